@@ -4,7 +4,7 @@ use std::time::{Instant, SystemTime};
 use clap::{App, AppSettings, Arg};
 use divvunspell::archive::ZipSpellerArchive;
 use divvunspell::speller::suggestion::Suggestion;
-use divvunspell::speller::{CaseHandlingConfig, SpellerConfig};
+use divvunspell::speller::{CaseHandlingConfig, HyphenPolicy, SpellerConfig};
 use indicatif::{ParallelProgressIterator, ProgressBar, ProgressStyle};
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use serde::Serialize;
@@ -14,7 +14,19 @@ static CFG: SpellerConfig = SpellerConfig {
     max_weight: Some(10000.0),
     beam: None,
     case_handling: Some(CaseHandlingConfig::default()),
+    case_policy: None,
     node_pool_size: 128,
+    with_analyses: false,
+    with_edits: false,
+    deadline: None,
+    cancel: None,
+    split_penalty: 10.0,
+    merge_penalty: 10.0,
+    confusion_threshold: 1.0,
+    language_model_weight: 0.5,
+    hyphen: HyphenPolicy::Whole,
+    diacritic_weight: None,
+    accept_without_diacritics: false,
 };
 
 fn load_words(
@@ -4,7 +4,12 @@ use box_format::BoxFileReader;
 
 use super::error::SpellerArchiveError;
 use super::meta::SpellerMetadata;
+use crate::speller::blocklist::Blocklist;
+use crate::speller::confusion::ConfusionSets;
+use crate::speller::language_model::NgramModel;
+use crate::speller::normalizer::Normalizer;
 use crate::speller::Speller;
+use crate::tokenizer::case_handling::CaseMapping;
 use crate::transducer::{
     thfst::{MemmapThfstChunkedTransducer, MemmapThfstTransducer},
     Transducer,
@@ -65,6 +70,34 @@ where
             U::from_path(&fs, "acceptor.default.thfst").map_err(SpellerArchiveError::Transducer)?;
 
         let speller = Speller::new(errmodel, acceptor);
+
+        if let Ok(file) = fs.open("blocklist.txt") {
+            let blocklist = Blocklist::from_reader(file).map_err(SpellerArchiveError::Io)?;
+            speller.blocklist_mut().extend(blocklist);
+        }
+
+        if let Ok(file) = fs.open("language_model.arpa") {
+            let model = NgramModel::from_arpa(file).map_err(SpellerArchiveError::LanguageModel)?;
+            speller.set_language_model(Some(model));
+        }
+
+        if let Ok(file) = fs.open("confusion_sets.txt") {
+            let sets = ConfusionSets::from_reader(file).map_err(SpellerArchiveError::Io)?;
+            *speller.confusion_sets_mut() = sets;
+        }
+
+        if let Some(metadata) = metadata.as_ref() {
+            speller.set_case_mapping(CaseMapping::from_locale(&metadata.info.locale));
+
+            if let Some(form) = metadata.info.normalization {
+                speller.set_normalization(Some(form));
+            }
+
+            if let Some(normalizer) = metadata.normalizer.as_ref() {
+                *speller.normalizer_mut() = Normalizer::new(normalizer.rules.clone());
+            }
+        }
+
         Ok(BoxSpellerArchive { speller, metadata })
     }
 
@@ -1,3 +1,4 @@
+use crate::speller::language_model::LanguageModelError;
 use crate::transducer::TransducerError;
 use std::fmt;
 use std::io::Error;
@@ -7,6 +8,7 @@ pub enum SpellerArchiveError {
     File(Error),
     Io(Error),
     Transducer(TransducerError),
+    LanguageModel(LanguageModelError),
     NoMetadata,
     UnsupportedCompressed,
     Unknown(u8),
@@ -18,6 +20,10 @@ impl SpellerArchiveError {
             SpellerArchiveError::File(e) => e,
             SpellerArchiveError::Io(e) => e,
             SpellerArchiveError::Transducer(e) => e.into_io_error(),
+            SpellerArchiveError::LanguageModel(LanguageModelError::Io(e)) => e,
+            SpellerArchiveError::LanguageModel(e) => {
+                Error::new(std::io::ErrorKind::InvalidData, format!("{}", e))
+            }
             SpellerArchiveError::NoMetadata => {
                 Error::new(std::io::ErrorKind::Other, "missing metadata")
             }
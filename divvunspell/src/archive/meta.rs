@@ -1,11 +1,16 @@
 use serde::{Deserialize, Serialize};
 use serde_xml_rs::{from_reader, Error, ParserConfig};
 
+use crate::speller::normalization::NormalizationForm;
+use crate::speller::normalizer::NormalizerRule;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SpellerMetadata {
     pub info: SpellerMetadataInfo,
     pub acceptor: SpellerMetadataAcceptor,
     pub errmodel: SpellerMetadataErrmodel,
+    #[serde(default)]
+    pub normalizer: Option<SpellerMetadataNormalizer>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -21,6 +26,9 @@ pub struct SpellerMetadataInfo {
     pub title: Vec<SpellerTitle>,
     pub description: String,
     pub producer: String,
+    /// The normalization form of the lexicon, `nfc` or `nfd`, if not left to detection.
+    #[serde(default)]
+    pub normalization: Option<NormalizationForm>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -39,6 +47,14 @@ pub struct SpellerMetadataErrmodel {
     pub description: String,
 }
 
+/// Rules for the speller's `Normalizer`, e.g.
+/// `<normalizer><rule type="replace" from="’" to="'"/></normalizer>`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SpellerMetadataNormalizer {
+    #[serde(rename = "rule", default)]
+    pub rules: Vec<NormalizerRule>,
+}
+
 impl std::str::FromStr for SpellerMetadata {
     type Err = Error;
 
@@ -98,3 +114,43 @@ fn test_xml_parse() {
     let s = SpellerMetadata::from_str(&xml_data).unwrap();
     println!("{:#?}", s);
 }
+
+#[test]
+fn test_normalizer_parse() {
+    use std::str::FromStr;
+
+    let xml_data = r##"<?xml version="1.0" encoding="UTF-8"?>
+        <hfstspeller dtdversion="1.0" hfstversion="3">
+        <info>
+            <locale>en</locale>
+            <title>English</title>
+            <description>English</description>
+            <producer>Someone</producer>
+        </info>
+        <acceptor type="general" id="acceptor.default.hfst">
+            <title>English</title>
+            <description>English</description>
+        </acceptor>
+        <errmodel id="errmodel.default.hfst">
+            <title>Edit distance</title>
+            <description>Edit distance</description>
+        </errmodel>
+        <normalizer>
+            <rule type="replace" from="’" to="'"/>
+            <rule type="collapse_repeats" min="3"/>
+        </normalizer>
+        </hfstspeller>
+    "##;
+
+    let s = SpellerMetadata::from_str(&xml_data).unwrap();
+    assert_eq!(
+        s.normalizer.unwrap().rules,
+        vec![
+            NormalizerRule::Replace {
+                from: "’".into(),
+                to: "'".into()
+            },
+            NormalizerRule::CollapseRepeats { min: 3 },
+        ]
+    );
+}
@@ -8,7 +8,12 @@ use std::sync::Arc;
 use super::error::SpellerArchiveError;
 use super::meta::SpellerMetadata;
 use super::{MmapRef, TempMmap};
+use crate::speller::blocklist::Blocklist;
+use crate::speller::confusion::ConfusionSets;
+use crate::speller::language_model::NgramModel;
+use crate::speller::normalizer::Normalizer;
 use crate::speller::Speller;
+use crate::tokenizer::case_handling::CaseMapping;
 use crate::transducer::hfst::HfstTransducer;
 
 pub type HfstZipSpeller =
@@ -78,6 +83,20 @@ impl ZipSpellerArchive {
             .map_err(SpellerArchiveError::Io)?;
         let errmodel_mmap = mmap_by_name(&mut file, &mut archive, &metadata.errmodel.id)
             .map_err(SpellerArchiveError::Io)?;
+        let blocklist = match archive.by_name("blocklist.txt") {
+            Ok(file) => Some(Blocklist::from_reader(file).map_err(SpellerArchiveError::Io)?),
+            Err(_) => None,
+        };
+        let language_model = match archive.by_name("language_model.arpa") {
+            Ok(file) => {
+                Some(NgramModel::from_arpa(file).map_err(SpellerArchiveError::LanguageModel)?)
+            }
+            Err(_) => None,
+        };
+        let confusion_sets = match archive.by_name("confusion_sets.txt") {
+            Ok(file) => Some(ConfusionSets::from_reader(file).map_err(SpellerArchiveError::Io)?),
+            Err(_) => None,
+        };
         drop(archive);
 
         let acceptor = HfstTransducer::from_mapped_memory(acceptor_mmap.map());
@@ -85,6 +104,28 @@ impl ZipSpellerArchive {
 
         let speller = Speller::new(errmodel, acceptor);
 
+        if let Some(blocklist) = blocklist {
+            speller.blocklist_mut().extend(blocklist);
+        }
+
+        if language_model.is_some() {
+            speller.set_language_model(language_model);
+        }
+
+        if let Some(sets) = confusion_sets {
+            *speller.confusion_sets_mut() = sets;
+        }
+
+        speller.set_case_mapping(CaseMapping::from_locale(&metadata.info.locale));
+
+        if let Some(form) = metadata.info.normalization {
+            speller.set_normalization(Some(form));
+        }
+
+        if let Some(normalizer) = metadata.normalizer.as_ref() {
+            *speller.normalizer_mut() = Normalizer::new(normalizer.rules.clone());
+        }
+
         Ok(ZipSpellerArchive { metadata, speller })
     }
 
@@ -0,0 +1,130 @@
+use crate::types::{SymbolNumber, Weight};
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+use std::cmp::Ordering;
+use std::cmp::Ordering::Equal;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Analysis {
+    pub value: SmolStr,
+    pub weight: Weight,
+    #[serde(default)]
+    pub lemma: SmolStr,
+    #[serde(default)]
+    pub tags: Vec<SmolStr>,
+}
+
+/// Tags are multicharacter symbols such as `+N` or `<sg>`. Multicharacter symbols made up
+/// only of letters (digraphs and the like) are part of the lemma.
+#[inline(always)]
+fn is_tag(symbol: &str) -> bool {
+    let mut chars = symbol.chars();
+    chars.next().is_some() && chars.next().is_some() && !symbol.chars().all(char::is_alphanumeric)
+}
+
+impl Analysis {
+    pub fn new(value: SmolStr, weight: Weight) -> Analysis {
+        Analysis {
+            value,
+            weight,
+            lemma: SmolStr::default(),
+            tags: vec![],
+        }
+    }
+
+    pub(crate) fn from_symbols(
+        key_table: &[SmolStr],
+        symbols: &[SymbolNumber],
+        weight: Weight,
+    ) -> Analysis {
+        let value: SmolStr = symbols.iter().map(|s| &*key_table[*s as usize]).collect();
+        let lemma: SmolStr = symbols
+            .iter()
+            .map(|s| &*key_table[*s as usize])
+            .take_while(|s| !is_tag(s))
+            .collect();
+        let tags = symbols
+            .iter()
+            .map(|s| &key_table[*s as usize])
+            .filter(|s| is_tag(s))
+            .cloned()
+            .collect();
+
+        Analysis {
+            value,
+            weight,
+            lemma,
+            tags,
+        }
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn weight(&self) -> Weight {
+        self.weight
+    }
+
+    pub fn lemma(&self) -> &str {
+        &self.lemma
+    }
+
+    pub fn tags(&self) -> &[SmolStr] {
+        &self.tags
+    }
+
+    /// The first tag of the analysis, which by convention is the part of speech.
+    pub fn pos(&self) -> Option<&str> {
+        self.tags.first().map(|x| &**x)
+    }
+}
+
+impl PartialOrd for Analysis {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Analysis {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let x = self.weight.partial_cmp(&other.weight).unwrap_or(Equal);
+
+        if let Equal = x {
+            return self.value.cmp(&other.value);
+        }
+
+        x
+    }
+}
+
+impl PartialEq for Analysis {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value && self.weight == other.weight
+    }
+}
+
+impl Eq for Analysis {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lemma_and_tags() {
+        let key_table: Vec<SmolStr> = vec!["", "c", "a", "t", "+N", "+Pl", "ch", "#"]
+            .into_iter()
+            .map(SmolStr::from)
+            .collect();
+
+        let analysis = Analysis::from_symbols(&key_table, &[1, 2, 3, 4, 5], 1.0);
+        assert_eq!(analysis.value(), "cat+N+Pl");
+        assert_eq!(analysis.lemma(), "cat");
+        assert_eq!(analysis.tags(), &["+N", "+Pl"]);
+        assert_eq!(analysis.pos(), Some("+N"));
+
+        let analysis = Analysis::from_symbols(&key_table, &[6, 2, 3, 7, 1, 2, 3, 4], 1.0);
+        assert_eq!(analysis.lemma(), "chat#cat");
+        assert_eq!(analysis.tags(), &["+N"]);
+    }
+}
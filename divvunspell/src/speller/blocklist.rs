@@ -0,0 +1,153 @@
+use std::io::{BufRead, BufReader, Read};
+
+use hashbrown::{HashMap, HashSet};
+use smol_str::SmolStr;
+
+use crate::speller::word_form::{FormedWords, WordForm};
+
+/// Words that must never be suggested, some of which are also rejected by `is_correct`
+/// even when the lexicon accepts them. Words are compared in the speller's `WordForm`,
+/// without regard to case.
+///
+/// As text, a blocklist has one word per line. A word prefixed with `!` is also rejected,
+/// and lines starting with `#` are comments. Archives may ship one as `blocklist.txt`.
+#[derive(Debug, Default, Clone)]
+pub struct Blocklist {
+    /// Words as they were given, and whether each is rejected.
+    words: HashMap<SmolStr, bool>,
+    blocked: HashSet<SmolStr>,
+    rejected: HashSet<SmolStr>,
+    form: WordForm,
+    form_generation: u64,
+}
+
+impl Blocklist {
+    pub fn new() -> Blocklist {
+        Blocklist::default()
+    }
+
+    pub fn from_reader<R: Read>(reader: R) -> std::io::Result<Blocklist> {
+        let mut blocklist = Blocklist::new();
+
+        for line in BufReader::new(reader).lines() {
+            let line = line?;
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            match line.strip_prefix('!') {
+                Some(word) => blocklist.insert(word.trim(), true),
+                None => blocklist.insert(line, false),
+            }
+        }
+
+        Ok(blocklist)
+    }
+
+    fn fold(&self, word: &str) -> SmolStr {
+        self.form.lower_case(&self.form.normalize(word))
+    }
+
+    fn insert_formed(&mut self, word: &str, reject: bool) {
+        let word = self.fold(word);
+
+        if reject {
+            self.rejected.insert(word.clone());
+        }
+
+        self.blocked.insert(word);
+    }
+
+    /// Blocks a word from being suggested and, if `reject` is set, from being accepted.
+    pub fn insert(&mut self, word: &str, reject: bool) {
+        let reject = reject || self.words.get(word).copied().unwrap_or(false);
+        self.words.insert(word.into(), reject);
+        self.insert_formed(word, reject);
+    }
+
+    /// Unblocks a word, and every other word written the same in the speller's form.
+    pub fn remove(&mut self, word: &str) -> bool {
+        let word = self.fold(word);
+        let form = self.form.clone();
+        self.words
+            .retain(|x, _| form.lower_case(&form.normalize(x)) != word);
+        self.rejected.remove(&word);
+        self.blocked.remove(&word)
+    }
+
+    /// Adds every word of `other` to this blocklist.
+    pub fn extend(&mut self, other: Blocklist) {
+        for (word, reject) in other.words.into_iter() {
+            self.insert(&word, reject);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocked.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocked.is_empty()
+    }
+
+    /// Whether a suggestion must be dropped. Suggestions spanning several words, from
+    /// splitting or hyphenation, are dropped if any of their words is blocked.
+    pub fn is_blocked(&self, suggestion: &str) -> bool {
+        if self.blocked.is_empty() {
+            return false;
+        }
+
+        let suggestion = self.form.lower_case(suggestion);
+
+        self.blocked.contains(&suggestion)
+            || suggestion
+                .split(|c: char| c.is_whitespace() || c == '-')
+                .any(|word| self.blocked.contains(word))
+    }
+
+    /// Whether a word, as the speller looks it up, must be reported as incorrect, whatever
+    /// the lexicon says.
+    pub fn is_rejected(&self, word: &str) -> bool {
+        !self.rejected.is_empty() && self.rejected.contains(&self.form.lower_case(word))
+    }
+}
+
+impl FormedWords for Blocklist {
+    fn form_generation(&self) -> u64 {
+        self.form_generation
+    }
+
+    fn set_form(&mut self, form: &WordForm, generation: u64) {
+        self.form = form.clone();
+        self.form_generation = generation;
+        self.blocked.clear();
+        self.rejected.clear();
+
+        let words: Vec<(SmolStr, bool)> = self.words.iter().map(|(x, y)| (x.clone(), *y)).collect();
+        for (word, reject) in words.iter() {
+            self.insert_formed(word, *reject);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_match() {
+        let text = "# deprecated\nfoo\n!Bar\n\n";
+        let blocklist = Blocklist::from_reader(text.as_bytes()).unwrap();
+
+        assert_eq!(blocklist.len(), 2);
+        assert!(blocklist.is_blocked("Foo"));
+        assert!(blocklist.is_blocked("bar"));
+        assert!(blocklist.is_blocked("a foo"));
+        assert!(blocklist.is_blocked("bar-baz"));
+        assert!(!blocklist.is_blocked("food"));
+        assert!(!blocklist.is_rejected("foo"));
+        assert!(blocklist.is_rejected("BAR"));
+    }
+}
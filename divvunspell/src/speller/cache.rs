@@ -0,0 +1,332 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use hashbrown::HashMap;
+use parking_lot::Mutex;
+use serde::Serialize;
+use smol_str::SmolStr;
+
+use super::{Speller, SpellerConfig};
+use crate::speller::suggestion::Suggestion;
+use crate::transducer::Transducer;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum CacheKey {
+    Correct(SmolStr, u64),
+    Suggest(SmolStr, u64),
+}
+
+#[derive(Debug, Clone)]
+enum CacheValue {
+    Correct(bool),
+    Suggest(Vec<Suggestion>),
+}
+
+/// A bounded map that evicts the least recently used entry when full.
+struct Lru<K, V> {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<K, (V, u64)>,
+    order: BTreeMap<u64, K>,
+}
+
+impl<K: Clone + Eq + Hash, V: Clone> Lru<K, V> {
+    fn new(capacity: usize) -> Lru<K, V> {
+        Lru {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn get(&mut self, key: &K) -> Option<V> {
+        let tick = self.tick + 1;
+        let (value, last_used) = self.entries.get_mut(key)?;
+
+        self.order.remove(last_used);
+        self.order.insert(tick, key.clone());
+        *last_used = tick;
+        self.tick = tick;
+
+        Some(value.clone())
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        self.tick += 1;
+
+        if let Some((_, last_used)) = self.entries.insert(key.clone(), (value, self.tick)) {
+            self.order.remove(&last_used);
+        } else if self.entries.len() > self.capacity {
+            let oldest = *self.order.keys().next().unwrap();
+            let oldest_key = self.order.remove(&oldest).unwrap();
+            self.entries.remove(&oldest_key);
+        }
+
+        self.order.insert(self.tick, key);
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    pub len: usize,
+    pub capacity: usize,
+}
+
+/// A `Speller` that remembers its most recent answers, keyed by word and configuration.
+///
+/// Answers for configurations with a `case_policy` are not cached, as policies cannot be
+/// told apart by value. Searches cut short by a deadline or cancellation are not cached
+/// either, and everything is forgotten when anything in the speller changes, as counted by
+/// `Speller::generation`.
+pub struct CachedSpeller<F, T: Transducer<F>, U: Transducer<F>>
+where
+    F: crate::vfs::File,
+{
+    speller: Arc<Speller<F, T, U>>,
+    lru: Mutex<Lru<CacheKey, CacheValue>>,
+    generation: AtomicU64,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl<F, T, U> CachedSpeller<F, T, U>
+where
+    F: crate::vfs::File,
+    T: Transducer<F>,
+    U: Transducer<F>,
+{
+    pub fn new(speller: Arc<Speller<F, T, U>>, capacity: usize) -> CachedSpeller<F, T, U> {
+        let generation = speller.generation();
+
+        CachedSpeller {
+            speller,
+            lru: Mutex::new(Lru::new(capacity)),
+            generation: AtomicU64::new(generation),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn speller(&self) -> &Arc<Speller<F, T, U>> {
+        &self.speller
+    }
+
+    pub fn is_correct(&self, word: &str) -> bool {
+        self.is_correct_with_config(word, &SpellerConfig::default())
+    }
+
+    pub fn is_correct_with_config(&self, word: &str, config: &SpellerConfig) -> bool {
+        let key = match config_hash(config) {
+            Some(hash) => CacheKey::Correct(word.into(), hash),
+            None => return self.speller.clone().is_correct_with_config(word, config),
+        };
+
+        if let Some(CacheValue::Correct(value)) = self.get(&key) {
+            return value;
+        }
+
+        let generation = self.generation.load(Ordering::Relaxed);
+        let value = self.speller.clone().is_correct_with_config(word, config);
+        self.insert(generation, key, CacheValue::Correct(value));
+        value
+    }
+
+    pub fn suggest(&self, word: &str) -> Vec<Suggestion> {
+        self.suggest_with_config(word, &SpellerConfig::default())
+    }
+
+    pub fn suggest_with_config(&self, word: &str, config: &SpellerConfig) -> Vec<Suggestion> {
+        let key = match config_hash(config) {
+            Some(hash) => CacheKey::Suggest(word.into(), hash),
+            None => return self.speller.clone().suggest_with_config(word, config),
+        };
+
+        if let Some(CacheValue::Suggest(value)) = self.get(&key) {
+            return value;
+        }
+
+        let generation = self.generation.load(Ordering::Relaxed);
+        let result = self.speller.clone().suggest_with_result(word, config);
+
+        if !result.is_partial {
+            self.insert(
+                generation,
+                key,
+                CacheValue::Suggest(result.suggestions.clone()),
+            );
+        }
+
+        result.suggestions
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let lru = self.lru.lock();
+
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            len: lru.len(),
+            capacity: lru.capacity,
+        }
+    }
+
+    /// Forgets every cached answer. Changes made through the speller are noticed without
+    /// this.
+    pub fn invalidate(&self) {
+        self.lru.lock().clear();
+    }
+
+    fn get(&self, key: &CacheKey) -> Option<CacheValue> {
+        let mut lru = self.lru.lock();
+        let generation = self.speller.generation();
+
+        if self.generation.swap(generation, Ordering::Relaxed) != generation {
+            lru.clear();
+        }
+
+        let value = lru.get(key);
+        drop(lru);
+
+        match value {
+            Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+
+        value
+    }
+
+    /// Caches an answer computed at `generation`, unless the speller has changed since.
+    fn insert(&self, generation: u64, key: CacheKey, value: CacheValue) {
+        let mut lru = self.lru.lock();
+
+        if self.speller.generation() == generation {
+            lru.insert(key, value);
+        }
+    }
+}
+
+/// Hashes the serializable part of a config, which is everything that affects the
+/// suggestions themselves but the case policy. `None` if the config has one.
+fn config_hash(config: &SpellerConfig) -> Option<u64> {
+    if config.case_policy.is_some() {
+        return None;
+    }
+
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_vec(config)
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::speller::normalizer::NormalizerRule;
+    use crate::speller::testing::speller;
+    use crate::tokenizer::case_handling::CaseMapping;
+
+    #[test]
+    fn speller_changes_invalidate() {
+        let cached = CachedSpeller::new(speller(&[("abc", 0.0), ("abd", 0.0)]), 16);
+
+        assert!(!cached.is_correct("abe"));
+        cached.speller().user_dictionary_mut().insert("abe");
+        assert!(cached.is_correct("abe"));
+
+        assert!(cached.is_correct("abc"));
+        cached.speller().blocklist_mut().insert("abc", true);
+        assert!(!cached.is_correct("abc"));
+
+        assert!(!cached.is_correct("abx"));
+        cached
+            .speller()
+            .normalizer_mut()
+            .push(NormalizerRule::Replace {
+                from: "x".into(),
+                to: "d".into(),
+            });
+        assert!(cached.is_correct("abx"));
+
+        let speller = cached.speller();
+        let changes: Vec<Box<dyn Fn()>> = vec![
+            Box::new(|| speller.set_case_mapping(CaseMapping::Turkic)),
+            Box::new(|| speller.set_normalization(None)),
+            Box::new(|| speller.set_language_model(None)),
+            Box::new(|| speller.confusion_sets_mut().insert(&["abc", "abd"])),
+        ];
+
+        for change in changes.iter() {
+            let generation = speller.generation();
+            change();
+            assert!(speller.generation() > generation);
+        }
+    }
+
+    #[test]
+    fn case_policies_are_not_cached() {
+        use crate::tokenizer::case_handling::{CaseHandler, CasePolicy};
+
+        #[derive(Debug)]
+        struct Exact;
+
+        impl CasePolicy for Exact {
+            fn accept_variants(&self, word: &str, _mapping: CaseMapping) -> Vec<SmolStr> {
+                vec![word.into()]
+            }
+
+            fn suggest_variants(&self, word: &str, mapping: CaseMapping) -> CaseHandler {
+                let mut handler = mapping.word_variants(word);
+                handler.words.truncate(1);
+                handler
+            }
+        }
+
+        let cached = CachedSpeller::new(speller(&[("abc", 0.0)]), 16);
+        assert!(cached.is_correct("Abc"));
+
+        let config = SpellerConfig {
+            case_policy: Some(Arc::new(Exact)),
+            ..SpellerConfig::default()
+        };
+        assert!(!cached.is_correct_with_config("Abc", &config));
+        assert!(!cached.is_correct_with_config("Abc", &config));
+        assert_eq!(cached.stats().len, 1);
+    }
+
+    #[test]
+    fn lru_evicts_least_recently_used() {
+        let mut lru = Lru::new(2);
+        lru.insert("a", 1);
+        lru.insert("b", 2);
+        assert_eq!(lru.get(&"a"), Some(1));
+
+        lru.insert("c", 3);
+        assert_eq!(lru.get(&"b"), None);
+        assert_eq!(lru.get(&"a"), Some(1));
+        assert_eq!(lru.get(&"c"), Some(3));
+
+        lru.insert("a", 4);
+        assert_eq!(lru.len(), 2);
+        assert_eq!(lru.get(&"a"), Some(4));
+    }
+}
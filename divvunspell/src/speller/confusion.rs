@@ -0,0 +1,198 @@
+use std::io::{BufRead, BufReader, Read};
+
+use hashbrown::HashMap;
+use smol_str::SmolStr;
+
+use crate::speller::word_form::{FormedWords, WordForm};
+
+/// Groups of correctly spelled words that are easily mistaken for one another, such as
+/// "their", "there" and "they're". A word may belong to one group only. Words are compared
+/// in the speller's `WordForm`, without regard to case.
+///
+/// As text, confusion sets have one group per line with the words separated by
+/// whitespace, and lines starting with `#` are comments. Archives may ship them as
+/// `confusion_sets.txt`.
+#[derive(Debug, Clone)]
+pub struct ConfusionSets {
+    /// Groups as they were given, in order.
+    groups: Vec<Vec<SmolStr>>,
+    /// Each group's words in the speller's form, and in lower case as given.
+    sets: Vec<Vec<(SmolStr, SmolStr)>>,
+    index: HashMap<SmolStr, usize>,
+    form: WordForm,
+    form_generation: u64,
+}
+
+impl Default for ConfusionSets {
+    fn default() -> ConfusionSets {
+        ConfusionSets {
+            groups: vec![],
+            sets: vec![],
+            index: HashMap::new(),
+            form: WordForm::default(),
+            form_generation: 0,
+        }
+    }
+}
+
+impl ConfusionSets {
+    pub fn new() -> ConfusionSets {
+        ConfusionSets::default()
+    }
+
+    pub fn from_reader<R: Read>(reader: R) -> std::io::Result<ConfusionSets> {
+        let mut sets = ConfusionSets::new();
+
+        for line in BufReader::new(reader).lines() {
+            let line = line?;
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            sets.insert(&line.split_whitespace().collect::<Vec<_>>());
+        }
+
+        Ok(sets)
+    }
+
+    /// Adds a group of words. Words already in another group are moved to this one.
+    pub fn insert(&mut self, words: &[&str]) {
+        self.groups
+            .push(words.iter().map(|x| SmolStr::from(*x)).collect());
+        self.insert_formed(words);
+    }
+
+    fn fold(&self, word: &str) -> SmolStr {
+        self.form.lower_case(&self.form.normalize(word))
+    }
+
+    fn insert_formed(&mut self, words: &[&str]) {
+        let index = self.sets.len();
+        let mut group: Vec<(SmolStr, SmolStr)> = Vec::with_capacity(words.len());
+
+        for word in words.iter() {
+            let folded = self.fold(word);
+
+            if !group.iter().any(|x| x.0 == folded) {
+                group.push((folded, self.form.lower_case(word)));
+            }
+        }
+
+        for (word, _) in group.iter() {
+            match self.index.insert(word.clone(), index) {
+                Some(old) if old != index => self.sets[old].retain(|x| x.0 != *word),
+                _ => {}
+            }
+        }
+
+        self.sets.push(group);
+    }
+
+    pub fn len(&self) -> usize {
+        self.sets.iter().filter(|x| x.len() > 1).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The other members of the group `word` belongs to, in lower case.
+    pub fn alternatives(&self, word: &str) -> Vec<SmolStr> {
+        let word = self.fold(word);
+
+        match self.index.get(&word) {
+            Some(&index) => self.sets[index]
+                .iter()
+                .filter(|x| x.0 != word)
+                .map(|x| x.1.clone())
+                .collect(),
+            None => vec![],
+        }
+    }
+}
+
+impl FormedWords for ConfusionSets {
+    fn form_generation(&self) -> u64 {
+        self.form_generation
+    }
+
+    fn set_form(&mut self, form: &WordForm, generation: u64) {
+        self.form = form.clone();
+        self.form_generation = generation;
+        self.sets.clear();
+        self.index.clear();
+
+        let groups = std::mem::take(&mut self.groups);
+        for group in groups.iter() {
+            self.insert_formed(&group.iter().map(|x| &**x).collect::<Vec<_>>());
+        }
+        self.groups = groups;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::speller::normalizer::{Normalizer, NormalizerRule};
+    use crate::tokenizer::case_handling::CaseMapping;
+
+    #[test]
+    fn groups() {
+        let text = "# English\ntheir there they're\nits it's\nthere then\n";
+        let sets = ConfusionSets::from_reader(text.as_bytes()).unwrap();
+
+        assert_eq!(sets.len(), 3);
+        assert_eq!(sets.alternatives("Their"), vec!["they're"]);
+        assert_eq!(sets.alternatives("there"), vec!["then"]);
+        assert_eq!(sets.alternatives("its"), vec!["it's"]);
+        assert!(sets.alternatives("cat").is_empty());
+    }
+
+    #[test]
+    fn repeated_words() {
+        let text = "there There their";
+        let sets = ConfusionSets::from_reader(text.as_bytes()).unwrap();
+
+        assert_eq!(sets.len(), 1);
+        assert_eq!(sets.alternatives("there"), vec!["their"]);
+        assert_eq!(sets.alternatives("their"), vec!["there"]);
+    }
+
+    #[test]
+    fn case_mapping() {
+        let mut sets = ConfusionSets::new();
+        sets.insert(&["Irmak", "ırmak", "kırmak"]);
+        assert_eq!(sets.alternatives("IRMAK"), vec!["ırmak", "kırmak"]);
+
+        // In Turkish, "IRMAK" and "ırmak" are the same word.
+        let form = WordForm {
+            case_mapping: CaseMapping::Turkic,
+            ..WordForm::default()
+        };
+        sets.set_form(&form, 1);
+        assert_eq!(sets.alternatives("IRMAK"), vec!["kırmak"]);
+    }
+
+    #[test]
+    fn word_form() {
+        let mut sets = ConfusionSets::new();
+        sets.insert(&["don’t", "done"]);
+        assert!(sets.alternatives("don't").is_empty());
+
+        // With apostrophes normalized, either spelling finds the group, and alternatives
+        // are given as they were added.
+        let form = WordForm {
+            normalizer: Normalizer::new(vec![NormalizerRule::Replace {
+                from: "’".into(),
+                to: "'".into(),
+            }]),
+            ..WordForm::default()
+        };
+        sets.set_form(&form, 1);
+        assert_eq!(sets.alternatives("don't"), vec!["done"]);
+        assert_eq!(sets.alternatives("Don’t"), vec!["done"]);
+        assert_eq!(sets.alternatives("Done"), vec!["don’t"]);
+    }
+}
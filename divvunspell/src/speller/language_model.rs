@@ -0,0 +1,281 @@
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+use hashbrown::HashMap;
+use smol_str::SmolStr;
+
+use crate::tokenizer::case_handling::CaseMapping;
+use crate::types::Weight;
+
+/// Log probability given to words the model has never seen, when it has no `<unk>`.
+const UNKNOWN_LOG_PROB: f32 = -7.0;
+
+#[derive(Debug)]
+pub enum LanguageModelError {
+    Io(std::io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl std::error::Error for LanguageModelError {}
+
+impl std::fmt::Display for LanguageModelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            LanguageModelError::Io(e) => write!(f, "{}", e),
+            LanguageModelError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Ngram {
+    log_prob: f32,
+    backoff: f32,
+}
+
+/// A backoff n-gram model read from the ARPA text format, for ranking suggestions by the
+/// words around them. Words are compared in lower case, as `case_mapping` writes it, which
+/// a speller sets to its own.
+#[derive(Debug, Clone)]
+pub struct NgramModel {
+    order: usize,
+    /// N-grams the model spells in lower case, which read the same in every case mapping.
+    ngrams: HashMap<SmolStr, Ngram>,
+    /// N-grams the model spells with capitals, as spelled, and in lower case.
+    cased: HashMap<SmolStr, Ngram>,
+    folded: HashMap<SmolStr, Ngram>,
+    case_mapping: CaseMapping,
+}
+
+impl NgramModel {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<NgramModel, LanguageModelError> {
+        let file = std::fs::File::open(path).map_err(LanguageModelError::Io)?;
+        NgramModel::from_arpa(file)
+    }
+
+    pub fn from_arpa<R: Read>(reader: R) -> Result<NgramModel, LanguageModelError> {
+        let mut order = 0;
+        let mut section = None;
+        let mut ngrams = HashMap::new();
+        let mut cased = HashMap::new();
+
+        for (index, line) in BufReader::new(reader).lines().enumerate() {
+            let line = line.map_err(LanguageModelError::Io)?;
+            let line = line.trim();
+            let parse_error = |message: &str| LanguageModelError::Parse {
+                line: index + 1,
+                message: message.to_string(),
+            };
+
+            if line.is_empty() || line.starts_with("ngram ") || line == "\\data\\" {
+                continue;
+            }
+
+            if line == "\\end\\" {
+                break;
+            }
+
+            if line.starts_with('\\') && line.ends_with("-grams:") {
+                let n = line[1..line.len() - "-grams:".len()]
+                    .parse::<usize>()
+                    .map_err(|_| parse_error("invalid section header"))?;
+                order = order.max(n);
+                section = Some(n);
+                continue;
+            }
+
+            let n = section.ok_or_else(|| parse_error("n-gram outside of a section"))?;
+            let mut fields = line.split_whitespace();
+            let log_prob = fields
+                .next()
+                .and_then(|x| x.parse::<f32>().ok())
+                .ok_or_else(|| parse_error("invalid log probability"))?;
+            let words: Vec<SmolStr> = fields.by_ref().take(n).map(SmolStr::from).collect();
+
+            if words.len() != n {
+                return Err(parse_error("too few words for section"));
+            }
+
+            let backoff = match fields.next() {
+                Some(x) => x
+                    .parse::<f32>()
+                    .map_err(|_| parse_error("invalid backoff weight"))?,
+                None => 0.0,
+            };
+
+            let key = key(&words);
+            let ngram = Ngram { log_prob, backoff };
+
+            if CaseMapping::Default.lower_case(&key) == key {
+                ngrams.insert(key, ngram);
+            } else {
+                cased.insert(key, ngram);
+            }
+        }
+
+        let mut model = NgramModel {
+            order,
+            ngrams,
+            cased,
+            folded: HashMap::new(),
+            case_mapping: CaseMapping::Default,
+        };
+        model.set_case_mapping(CaseMapping::Default);
+        Ok(model)
+    }
+
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    pub fn case_mapping(&self) -> CaseMapping {
+        self.case_mapping
+    }
+
+    /// Lower cases words with `mapping`. Where n-grams the model spells differently read
+    /// the same in lower case, the one spelled in lower case wins, then the likeliest.
+    pub fn set_case_mapping(&mut self, mapping: CaseMapping) {
+        self.case_mapping = mapping;
+        self.folded.clear();
+
+        for (key, ngram) in self.cased.iter() {
+            let key = mapping.lower_case(key);
+
+            if self.ngrams.contains_key(&key) {
+                continue;
+            }
+
+            match self.folded.get(&key) {
+                Some(x) if x.log_prob >= ngram.log_prob => {}
+                _ => {
+                    self.folded.insert(key, *ngram);
+                }
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn get(&self, key: &str) -> Option<&Ngram> {
+        match self.ngrams.get(key) {
+            Some(ngram) => Some(ngram),
+            None if self.folded.is_empty() => None,
+            None => self.folded.get(key),
+        }
+    }
+
+    /// The log10 probability of `word` following `history`, backing off to shorter
+    /// histories as needed. Only the last `order - 1` words of the history are used.
+    pub fn log_prob(&self, history: &[SmolStr], word: &str) -> f32 {
+        let history: Vec<SmolStr> = history
+            .iter()
+            .map(|x| self.case_mapping.lower_case(x))
+            .collect();
+        self.lowercase_log_prob(&history, &self.case_mapping.lower_case(word))
+    }
+
+    #[inline(always)]
+    fn lowercase_log_prob(&self, history: &[SmolStr], word: &SmolStr) -> f32 {
+        let history = &history[history.len().saturating_sub(self.order.saturating_sub(1))..];
+        self.backoff_log_prob(history, word)
+    }
+
+    fn backoff_log_prob(&self, history: &[SmolStr], word: &SmolStr) -> f32 {
+        if history.is_empty() {
+            return self
+                .get(word)
+                .or_else(|| self.get("<unk>"))
+                .map(|x| x.log_prob)
+                .unwrap_or(UNKNOWN_LOG_PROB);
+        }
+
+        let mut words = history.to_vec();
+        words.push(word.clone());
+
+        if let Some(ngram) = self.get(&key(&words)) {
+            return ngram.log_prob;
+        }
+
+        let backoff = self.get(&key(history)).map(|x| x.backoff).unwrap_or(0.0);
+
+        backoff + self.backoff_log_prob(&history[1..], word)
+    }
+
+    /// The log10 probability of a whole sequence of words, each conditioned on those before.
+    pub fn sequence_log_prob(&self, words: &[SmolStr]) -> f32 {
+        let words: Vec<SmolStr> = words
+            .iter()
+            .map(|x| self.case_mapping.lower_case(x))
+            .collect();
+
+        (0..words.len())
+            .map(|i| self.lowercase_log_prob(&words[..i], &words[i]))
+            .sum()
+    }
+
+    /// Converts a log10 probability into a weight on the same scale as transducer weights,
+    /// which are negative natural logarithms.
+    pub fn weight(log_prob: f32) -> Weight {
+        -log_prob * std::f32::consts::LN_10
+    }
+}
+
+#[inline(always)]
+fn key(words: &[SmolStr]) -> SmolStr {
+    let mut key = String::new();
+
+    for (i, word) in words.iter().enumerate() {
+        if i > 0 {
+            key.push(' ');
+        }
+        key.push_str(word);
+    }
+
+    key.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARPA: &str = "\\data\\
+ngram 1=3
+ngram 2=1
+
+\\1-grams:
+-1.0\ta\t-0.5
+-1.5\tlot
+-2.0\tlotto
+
+\\2-grams:
+-0.2\ta lot
+
+\\end\\
+";
+
+    #[test]
+    fn backoff() {
+        let model = NgramModel::from_arpa(ARPA.as_bytes()).unwrap();
+        assert_eq!(model.order(), 2);
+
+        let history = vec![SmolStr::from("A")];
+        assert_eq!(model.log_prob(&history, "lot"), -0.2);
+        assert_eq!(model.log_prob(&history, "lotto"), -2.5);
+        assert_eq!(model.log_prob(&[], "nothing"), UNKNOWN_LOG_PROB);
+        assert_eq!(
+            model.sequence_log_prob(&["a".into(), "lot".into()]),
+            -1.0 + -0.2
+        );
+    }
+
+    #[test]
+    fn case_mapping() {
+        let arpa = "\\data\\\nngram 1=2\n\n\\1-grams:\n-1.0\tIşık\n-2.0\tışık\n\n\\end\\\n";
+        let mut model = NgramModel::from_arpa(arpa.as_bytes()).unwrap();
+        assert_eq!(model.log_prob(&[], "ışık"), -2.0);
+        assert_eq!(model.log_prob(&[], "IŞIK"), UNKNOWN_LOG_PROB);
+
+        model.set_case_mapping(CaseMapping::Turkic);
+        assert_eq!(model.log_prob(&[], "IŞIK"), -2.0);
+        assert_eq!(model.log_prob(&[], "Işık"), -2.0);
+    }
+}
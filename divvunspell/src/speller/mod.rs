@@ -1,25 +1,76 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
 use std::f32;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering as AtomicOrdering};
 use std::sync::Arc;
+use std::time::Instant;
 
-use hashbrown::HashMap;
+use hashbrown::{HashMap, HashSet};
+use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 use serde::{Deserialize, Serialize};
 use smol_str::SmolStr;
+use unic_segment::GraphemeIndices;
 use unic_ucd_category::GeneralCategory;
 
-use self::worker::SpellerWorker;
-use crate::speller::suggestion::Suggestion;
-use crate::tokenizer::case_handling::CaseHandler;
+use self::symbol_trie::SymbolTrie;
+use self::worker::{InputSymbols, SpellerWorker, SuggestionSearch};
+use crate::speller::analysis::Analysis;
+use crate::speller::blocklist::Blocklist;
+use crate::speller::confusion::ConfusionSets;
+use crate::speller::language_model::NgramModel;
+use crate::speller::normalization::NormalizationForm;
+use crate::speller::normalizer::{Normalized, Normalizer};
+use crate::speller::suggestion::{Edit, Suggestion, SuggestionResult};
+use crate::speller::user_dictionary::UserDictionary;
+use crate::speller::word_form::{FormedWords, WordForm};
+use crate::tokenizer::case_handling::{
+    CaseHandler, CaseMapping, CaseMode, CaseMutation, CasePolicy,
+};
+use crate::tokenizer::WordContext;
 use crate::transducer::Transducer;
 use crate::types::{SymbolNumber, Weight};
 
+pub mod analysis;
+pub mod blocklist;
+pub mod cache;
+pub mod confusion;
+pub mod language_model;
+pub mod normalization;
+pub mod normalizer;
+pub mod rerank;
 pub mod suggestion;
+mod symbol_trie;
+#[cfg(test)]
+pub(crate) mod testing;
+pub mod user_dictionary;
+pub mod word_form;
 mod worker;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct CaseHandlingConfig {
-    start_penalty: f32,
-    end_penalty: f32,
-    mid_penalty: f32,
+    /// Penalty for a merged suggestion whose first letter differs from its variant's.
+    pub start_penalty: f32,
+    /// Penalty for a merged suggestion whose last letter differs from its variant's.
+    pub end_penalty: f32,
+    /// Penalty per edit between the input, its variant and the suggestion.
+    pub mid_penalty: f32,
+    /// Accept a word in any case the lexicon has it in, e.g. "pARIS" for "Paris".
+    #[serde(default)]
+    pub accept_any_case: bool,
+    /// Reject a capitalized or mixed case word that the lexicon only has in lower case,
+    /// e.g. "Cat". All-caps words are still accepted, e.g. "CAT". Takes precedence over
+    /// `accept_any_case`.
+    #[serde(default)]
+    pub reject_lowercase_only: bool,
+    /// Also look up all-caps words with hyphens with each part capitalized, e.g.
+    /// "Sámi-English" for "SÁMI-ENGLISH".
+    #[serde(default)]
+    pub title_case_parts: bool,
+    /// How the suggestions of the variants are combined, overriding the mode picked for
+    /// the input.
+    #[serde(default)]
+    pub mode: Option<CaseMode>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -28,7 +79,82 @@ pub struct SpellerConfig {
     pub max_weight: Option<Weight>,
     pub beam: Option<Weight>,
     pub case_handling: Option<CaseHandlingConfig>,
+    /// Replaces `case_handling` in deciding which case variants are checked and suggested
+    /// for. Merge penalties still come from `case_handling`, or its defaults if unset.
+    #[serde(skip)]
+    pub case_policy: Option<Arc<dyn CasePolicy>>,
     pub node_pool_size: usize,
+    /// Attach the lexicon's analyses to each suggestion. Only meaningful when the lexicon
+    /// is an analyser; the suggestion value is then taken from its input side.
+    #[serde(default)]
+    pub with_analyses: bool,
+    /// Attach the error model edits that produced each suggestion.
+    #[serde(default)]
+    pub with_edits: bool,
+    /// Stop searching at this instant and return the best suggestions found so far.
+    #[serde(skip)]
+    pub deadline: Option<Instant>,
+    /// Stop searching once this is set, returning the best suggestions found so far.
+    #[serde(skip)]
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// Weight of suggesting that a word be split in two, in `Speller::suggest_in_context`.
+    #[serde(default = "default_split_penalty")]
+    pub split_penalty: Weight,
+    /// Weight of suggesting that a word be joined with a neighbouring word, in
+    /// `Speller::suggest_in_context`.
+    #[serde(default = "default_merge_penalty")]
+    pub merge_penalty: Weight,
+    /// How much likelier, in log10 units, another member of a confusion set must be in
+    /// context before `Speller::check_in_context` flags a word.
+    #[serde(default = "default_confusion_threshold")]
+    pub confusion_threshold: Weight,
+    /// How much the language model counts against the transducer weight in
+    /// `Speller::suggest_in_context`, from 0 (not at all) to 1 (only the language model).
+    #[serde(default = "default_language_model_weight")]
+    pub language_model_weight: Weight,
+    /// How words containing hyphens are checked and corrected.
+    #[serde(default)]
+    pub hyphen: HyphenPolicy,
+    /// Weight of swapping a letter for the same letter with other diacritics, such as "c"
+    /// for "č", in suggestion search. The swaps are made alongside the error model, so
+    /// they work even if it has no such edits. `None` leaves diacritics to the error model.
+    #[serde(default)]
+    pub diacritic_weight: Option<Weight>,
+    /// Accept words that are in the lexicon once diacritics are ignored, so that "cahppes"
+    /// passes for "čáhppes".
+    #[serde(default)]
+    pub accept_without_diacritics: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HyphenPolicy {
+    /// Hyphenated words are only accepted as a whole, like any other word.
+    Whole,
+    /// Hyphenated words the lexicon lacks are accepted if every part is, and are corrected
+    /// part by part.
+    Parts,
+}
+
+impl Default for HyphenPolicy {
+    fn default() -> HyphenPolicy {
+        HyphenPolicy::Whole
+    }
+}
+
+fn default_split_penalty() -> Weight {
+    SpellerConfig::default().split_penalty
+}
+
+fn default_merge_penalty() -> Weight {
+    SpellerConfig::default().merge_penalty
+}
+
+fn default_language_model_weight() -> Weight {
+    SpellerConfig::default().language_model_weight
+}
+
+fn default_confusion_threshold() -> Weight {
+    SpellerConfig::default().confusion_threshold
 }
 
 impl SpellerConfig {
@@ -38,9 +164,54 @@ impl SpellerConfig {
             max_weight: Some(10000.0),
             beam: None,
             case_handling: Some(CaseHandlingConfig::default()),
+            case_policy: None,
             node_pool_size: 128,
+            with_analyses: false,
+            with_edits: false,
+            deadline: None,
+            cancel: None,
+            split_penalty: 10.0,
+            merge_penalty: 10.0,
+            confusion_threshold: 1.0,
+            language_model_weight: 0.5,
+            hyphen: HyphenPolicy::Whole,
+            diacritic_weight: None,
+            accept_without_diacritics: false,
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn is_interrupted(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .map(|x| x.load(AtomicOrdering::Relaxed))
+            .unwrap_or(false)
+            || self.deadline.map(|x| Instant::now() >= x).unwrap_or(false)
+    }
+
+    /// Spellings of `word` to check, following `case_policy` or `case_handling`.
+    fn accept_variants(&self, word: &str, mapping: CaseMapping) -> Vec<SmolStr> {
+        match (self.case_policy.as_ref(), self.case_handling.as_ref()) {
+            (Some(policy), _) => policy.accept_variants(word, mapping),
+            (None, Some(case_handling)) => case_handling.accept_variants(word, mapping),
+            (None, None) => mapping.word_variants(word).words,
+        }
+    }
+
+    /// Spellings of `word` to suggest for, or `None` if case is not handled at all.
+    fn suggest_variants(&self, word: &str, mapping: CaseMapping) -> Option<CaseHandler> {
+        match (self.case_policy.as_ref(), self.case_handling.as_ref()) {
+            (Some(policy), _) => Some(policy.suggest_variants(word, mapping)),
+            (None, Some(case_handling)) => Some(case_handling.suggest_variants(word, mapping)),
+            (None, None) => None,
         }
     }
+
+    fn case_penalties(&self) -> CaseHandlingConfig {
+        self.case_handling
+            .clone()
+            .unwrap_or(CaseHandlingConfig::default())
+    }
 }
 
 impl CaseHandlingConfig {
@@ -49,10 +220,212 @@ impl CaseHandlingConfig {
             start_penalty: 10.0,
             end_penalty: 10.0,
             mid_penalty: 5.0,
+            accept_any_case: false,
+            reject_lowercase_only: false,
+            title_case_parts: false,
+            mode: None,
+        }
+    }
+
+    fn add_title_case(&self, word: &str, mapping: CaseMapping, words: &mut Vec<SmolStr>) {
+        if self.title_case_parts && word.contains('-') && mapping.is_all_caps(word) {
+            let title = mapping.title_case(word);
+
+            if !words.contains(&title) {
+                words.insert(1.min(words.len()), title);
+            }
         }
     }
 }
 
+impl CasePolicy for CaseHandlingConfig {
+    fn accept_variants(&self, word: &str, mapping: CaseMapping) -> Vec<SmolStr> {
+        let mut words = mapping.word_variants(word).words;
+        self.add_title_case(word, mapping, &mut words);
+
+        if self.accept_any_case {
+            let lower = mapping.lower_case(word);
+
+            for variant in [mapping.upper_case(word), mapping.upper_first(&lower), lower].iter() {
+                if !words.contains(variant) {
+                    words.push(variant.clone());
+                }
+            }
+        }
+
+        if self.reject_lowercase_only
+            && mapping.lower_case(word) != word
+            && !mapping.is_all_caps(word)
+        {
+            words.retain(|x| x == word || mapping.lower_case(x) != *x);
+        }
+
+        words
+    }
+
+    fn suggest_variants(&self, word: &str, mapping: CaseMapping) -> CaseHandler {
+        let mut case = mapping.word_variants(word);
+        self.add_title_case(word, mapping, &mut case.words);
+
+        if let Some(mode) = self.mode {
+            case.mode = mode;
+        }
+
+        case
+    }
+}
+
+/// The config words are checked with: only case and diacritics handling apply, as the
+/// search limits are for suggestions, and a correct word is correct however heavy it is.
+fn accept_config(config: &SpellerConfig) -> SpellerConfig {
+    SpellerConfig {
+        accept_without_diacritics: config.accept_without_diacritics,
+        ..SpellerConfig::default()
+    }
+}
+
+/// How many times `n_best` suggestions the search keeps for a language model to rerank, so
+/// that suggestions the model favours are not cut before it sees them.
+const RERANK_BEAM: usize = 4;
+
+/// The words around the cursor in text order, with a suggestion in place of the current word
+/// and of any neighbour it absorbs.
+fn context_words(context: &WordContext, suggestion: &Suggestion) -> Vec<SmolStr> {
+    use crate::speller::suggestion::Merge;
+
+    let word = |x: &Option<(usize, String)>| x.as_ref().map(|(_, w)| SmolStr::from(&**w));
+    let first_before = match suggestion.merge {
+        Some(Merge::Previous) => None,
+        _ => word(&context.first_before),
+    };
+    let first_after = match suggestion.merge {
+        Some(Merge::Next) => None,
+        _ => word(&context.first_after),
+    };
+
+    word(&context.second_before)
+        .into_iter()
+        .chain(first_before)
+        .chain(suggestion.value().split_whitespace().map(SmolStr::from))
+        .chain(first_after)
+        .chain(word(&context.second_after))
+        .collect()
+}
+
+/// Maps each symbol of a suggestion to the input symbol it was copied from or substituted
+/// for, by following the error model edits between them. Returns `None` if the edits do
+/// not line up with `input_len` and `output_len` symbols.
+fn edit_alignment(
+    input_len: usize,
+    output_len: usize,
+    edits: &[Edit],
+) -> Option<Vec<Option<usize>>> {
+    let mut alignment = Vec::with_capacity(output_len);
+    let (mut input, mut output) = (0, 0);
+
+    for edit in edits.iter() {
+        let (input_range, output_range) = (edit.input_range(), edit.output_range());
+
+        if input_range.start < input
+            || output_range.start < output
+            || input_range.start - input != output_range.start - output
+        {
+            return None;
+        }
+
+        while output < output_range.start {
+            alignment.push(Some(input));
+            input += 1;
+            output += 1;
+        }
+
+        for i in 0..output_range.len() {
+            alignment.push(if i < input_range.len() {
+                Some(input + i)
+            } else {
+                None
+            });
+        }
+
+        input = input_range.end;
+        output = output_range.end;
+    }
+
+    if input > input_len || output > output_len || input_len - input != output_len - output {
+        return None;
+    }
+
+    alignment.extend((input..input_len).map(Some));
+    Some(alignment)
+}
+
+/// The grapheme clusters each of `symbols`, which spell out `word`, falls in.
+fn symbol_clusters(word: &str, symbols: &[SmolStr]) -> Vec<std::ops::Range<usize>> {
+    let starts: Vec<usize> = GraphemeIndices::new(word).map(|(i, _)| i).collect();
+    let cluster_at = |byte: usize| match starts.binary_search(&byte) {
+        Ok(index) => index,
+        Err(index) => index - 1,
+    };
+    let mut ranges = Vec::with_capacity(symbols.len());
+    let mut start = 0;
+
+    for symbol in symbols.iter() {
+        let end = start + symbol.len();
+        ranges.push(cluster_at(start)..cluster_at(end - 1) + 1);
+        start = end;
+    }
+
+    ranges
+}
+
+/// Turns an alignment of the symbols of `output` with those of `input` into one of their
+/// grapheme clusters. A cluster takes the cluster at the same place in the input symbol
+/// its symbol is aligned with, or the last cluster of a shorter one.
+fn cluster_alignment(
+    input: (&str, &[SmolStr]),
+    output: (&str, &[SmolStr]),
+    alignment: &[Option<usize>],
+) -> Vec<Option<usize>> {
+    let input_clusters = symbol_clusters(input.0, input.1);
+    let output_clusters = symbol_clusters(output.0, output.1);
+    let cluster_count = output_clusters.last().map(|x| x.end).unwrap_or(0);
+
+    (0..cluster_count)
+        .map(|cluster| {
+            let symbol = output_clusters.iter().position(|x| x.contains(&cluster))?;
+            let aligned = &input_clusters[alignment[symbol]?];
+            let offset = cluster - output_clusters[symbol].start;
+            Some((aligned.start + offset).min(aligned.end - 1))
+        })
+        .collect()
+}
+
+/// The extra weight of a suggestion found for `word`, a case variant of `original`, when
+/// the suggestions of all variants are merged.
+fn case_penalty(
+    case_handling: &CaseHandlingConfig,
+    original: &str,
+    word: &str,
+    suggestion: &str,
+) -> Weight {
+    let penalty_start = if !suggestion.starts_with(word.chars().next().unwrap()) {
+        case_handling.start_penalty
+    } else {
+        0.0
+    };
+    let penalty_end = if !suggestion.ends_with(word.chars().rev().next().unwrap()) {
+        case_handling.end_penalty
+    } else {
+        0.0
+    };
+
+    let distance =
+        strsim::damerau_levenshtein(original, word) + strsim::damerau_levenshtein(word, suggestion);
+    let penalty_middle = case_handling.mid_penalty * distance as f32;
+
+    penalty_start + penalty_end + penalty_middle
+}
+
 #[derive(Debug)]
 pub struct Speller<F, T: Transducer<F>, U: Transducer<F>>
 where
@@ -61,9 +434,54 @@ where
     mutator: T,
     lexicon: U,
     alphabet_translator: Vec<SymbolNumber>,
+    input_symbols: SymbolTrie,
+    diacritic_variants: Vec<Vec<SymbolNumber>>,
+    user_dictionary: RwLock<UserDictionary>,
+    blocklist: RwLock<Blocklist>,
+    language_model: RwLock<Option<Arc<NgramModel>>>,
+    confusion_sets: RwLock<ConfusionSets>,
+    case_mapping: RwLock<CaseMapping>,
+    normalization: RwLock<Option<NormalizationForm>>,
+    normalizer: RwLock<Normalizer>,
+    generation: AtomicU64,
+    /// Like `generation`, but only for changes to the speller's `WordForm`.
+    form_generation: AtomicU64,
     _file: std::marker::PhantomData<F>,
 }
 
+/// Write access to a part of a `Speller`. Releasing it counts as a change to the speller,
+/// as seen by `Speller::generation`.
+pub struct SpellerWriteGuard<'a, T> {
+    guard: RwLockWriteGuard<'a, T>,
+    generation: &'a AtomicU64,
+    form_generation: Option<&'a AtomicU64>,
+}
+
+impl<'a, T> Deref for SpellerWriteGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<'a, T> DerefMut for SpellerWriteGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<'a, T> Drop for SpellerWriteGuard<'a, T> {
+    fn drop(&mut self) {
+        // Still under the lock, so nothing can read the change before it is counted.
+        self.generation.fetch_add(1, AtomicOrdering::SeqCst);
+
+        if let Some(form_generation) = self.form_generation {
+            form_generation.fetch_add(1, AtomicOrdering::SeqCst);
+        }
+    }
+}
+
 impl<F, T, U> Speller<F, T, U>
 where
     F: crate::vfs::File,
@@ -72,15 +490,189 @@ where
 {
     pub fn new(mutator: T, mut lexicon: U) -> Arc<Speller<F, T, U>> {
         let alphabet_translator = lexicon.mut_alphabet().create_translator_from(&mutator);
+        let input_symbols = SymbolTrie::new(mutator.alphabet().string_to_symbol());
+        let normalization = NormalizationForm::detect(lexicon.alphabet().key_table());
+        let diacritic_variants = normalization::diacritic_variants(
+            mutator.alphabet().key_table(),
+            lexicon.alphabet().key_table(),
+        );
 
         Arc::new(Speller {
             mutator,
             lexicon,
             alphabet_translator,
+            input_symbols,
+            diacritic_variants,
+            user_dictionary: RwLock::new(UserDictionary::new()),
+            blocklist: RwLock::new(Blocklist::new()),
+            language_model: RwLock::new(None),
+            confusion_sets: RwLock::new(ConfusionSets::new()),
+            case_mapping: RwLock::new(CaseMapping::Default),
+            normalization: RwLock::new(normalization),
+            normalizer: RwLock::new(Normalizer::default()),
+            generation: AtomicU64::new(0),
+            form_generation: AtomicU64::new(1),
             _file: std::marker::PhantomData::<F>,
         })
     }
 
+    /// A count of changes to the speller's settings and word lists, which moves on whenever
+    /// something that may change its answers is set or written to.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(AtomicOrdering::SeqCst)
+    }
+
+    fn write<'a, V>(&'a self, lock: &'a RwLock<V>) -> SpellerWriteGuard<'a, V> {
+        SpellerWriteGuard {
+            guard: lock.write(),
+            generation: &self.generation,
+            form_generation: None,
+        }
+    }
+
+    /// Like `write`, for a setting that is part of the speller's `WordForm`.
+    fn write_form<'a, V>(&'a self, lock: &'a RwLock<V>) -> SpellerWriteGuard<'a, V> {
+        SpellerWriteGuard {
+            guard: lock.write(),
+            generation: &self.generation,
+            form_generation: Some(&self.form_generation),
+        }
+    }
+
+    /// How words are written for lookup, which word lists kept beside the lexicon follow.
+    pub fn word_form(&self) -> WordForm {
+        WordForm {
+            case_mapping: self.case_mapping(),
+            normalization: self.normalization(),
+            normalizer: self.normalizer().clone(),
+        }
+    }
+
+    /// Brings a word list up to date with the speller's `WordForm`, if it has changed, or
+    /// the list has been replaced, since the list last saw it.
+    fn update_form<V: FormedWords>(&self, lock: &RwLock<V>) {
+        let generation = self.form_generation.load(AtomicOrdering::SeqCst);
+
+        if lock.read().form_generation() != generation {
+            let form = self.word_form();
+            let mut words = lock.write();
+
+            if words.form_generation() != generation {
+                words.set_form(&form, generation);
+            }
+        }
+    }
+
+    fn read_formed<'a, V: FormedWords>(&'a self, lock: &'a RwLock<V>) -> RwLockReadGuard<'a, V> {
+        self.update_form(lock);
+        lock.read()
+    }
+
+    fn write_formed<'a, V: FormedWords>(&'a self, lock: &'a RwLock<V>) -> SpellerWriteGuard<'a, V> {
+        self.update_form(lock);
+        self.write(lock)
+    }
+
+    /// Words added at runtime, which are accepted and suggested alongside the lexicon.
+    pub fn user_dictionary(&self) -> RwLockReadGuard<'_, UserDictionary> {
+        self.read_formed(&self.user_dictionary)
+    }
+
+    pub fn user_dictionary_mut(&self) -> SpellerWriteGuard<'_, UserDictionary> {
+        self.write_formed(&self.user_dictionary)
+    }
+
+    /// Words that are never suggested, and optionally never accepted.
+    pub fn blocklist(&self) -> RwLockReadGuard<'_, Blocklist> {
+        self.read_formed(&self.blocklist)
+    }
+
+    pub fn blocklist_mut(&self) -> SpellerWriteGuard<'_, Blocklist> {
+        self.write_formed(&self.blocklist)
+    }
+
+    /// The n-gram model `suggest_in_context` ranks suggestions with, if any.
+    pub fn language_model(&self) -> Option<Arc<NgramModel>> {
+        self.language_model.read().clone()
+    }
+
+    pub fn set_language_model(&self, model: Option<NgramModel>) {
+        let mapping = self.case_mapping();
+        *self.write(&self.language_model) = model.map(|mut x| {
+            x.set_case_mapping(mapping);
+            Arc::new(x)
+        });
+    }
+
+    /// Groups of easily confused words that `check_in_context` tells apart.
+    pub fn confusion_sets(&self) -> RwLockReadGuard<'_, ConfusionSets> {
+        self.read_formed(&self.confusion_sets)
+    }
+
+    pub fn confusion_sets_mut(&self) -> SpellerWriteGuard<'_, ConfusionSets> {
+        self.write_formed(&self.confusion_sets)
+    }
+
+    /// How case variants of words are formed, which archives set from their locale.
+    pub fn case_mapping(&self) -> CaseMapping {
+        *self.case_mapping.read()
+    }
+
+    pub fn set_case_mapping(&self, mapping: CaseMapping) {
+        *self.write_form(&self.case_mapping) = mapping;
+
+        if let Some(model) = self.language_model.write().as_mut() {
+            Arc::make_mut(model).set_case_mapping(mapping);
+        }
+    }
+
+    /// The Unicode normalization form of the lexicon, detected from its alphabet unless
+    /// set. Words are converted to it before lookup, and suggestions are converted back to
+    /// the form of the word, so suggestion edits index the converted word.
+    pub fn normalization(&self) -> Option<NormalizationForm> {
+        *self.normalization.read()
+    }
+
+    pub fn set_normalization(&self, form: Option<NormalizationForm>) {
+        *self.write_form(&self.normalization) = form;
+    }
+
+    /// Rules run over words before lookup, after conversion to the lexicon's
+    /// normalization form.
+    pub fn normalizer(&self) -> RwLockReadGuard<'_, Normalizer> {
+        self.normalizer.read()
+    }
+
+    pub fn normalizer_mut(&self) -> SpellerWriteGuard<'_, Normalizer> {
+        self.write_form(&self.normalizer)
+    }
+
+    /// Converts `word` to the lexicon's normalization form and runs the normalizer over it.
+    fn normalize_input(&self, word: &str) -> NormalizedInput {
+        let (word, form) = match self.normalization() {
+            Some(form) => {
+                let input_form = NormalizationForm::of(word).filter(|x| *x != form);
+                (form.normalize(word), input_form)
+            }
+            None => (word.into(), None),
+        };
+
+        NormalizedInput {
+            normalized: self.normalizer().normalize(&word),
+            form,
+        }
+    }
+
+    /// Undoes `normalize_input` on a suggestion.
+    fn restore_output(&self, input: &NormalizedInput, value: &str) -> SmolStr {
+        let value = self.normalizer().restore(&input.normalized, value);
+
+        match input.form {
+            Some(form) => form.normalize(&value),
+            None => value,
+        }
+    }
+
     pub fn mutator(&self) -> &T {
         &self.mutator
     }
@@ -93,26 +685,141 @@ where
         &self.alphabet_translator
     }
 
-    fn to_input_vec(&self, word: &str) -> Vec<SymbolNumber> {
-        let alphabet = self.mutator().alphabet();
-        let key_table = alphabet.key_table();
+    /// Lexicon symbols that are the letter of mutator symbol `sym` with other diacritics.
+    fn diacritic_variants(&self, sym: SymbolNumber) -> &[SymbolNumber] {
+        self.diacritic_variants
+            .get(sym as usize)
+            .map(|x| &**x)
+            .unwrap_or(&[])
+    }
 
-        word.chars()
-            .map(|ch| {
-                let s = ch.to_string();
-                key_table
-                    .iter()
-                    .position(|x| x == &s)
-                    .map(|x| x as u16)
-                    .unwrap_or_else(|| alphabet.unknown().unwrap_or(0u16))
-            })
-            .collect()
+    /// Recases a suggestion for a case variant back to the case of the original word. Mixed
+    /// case follows the edits that produced the suggestion, if there are any, to line the
+    /// suggestion up with the input symbol by symbol.
+    fn recase(&self, mutation: &CaseMutation, value: &str, edits: Option<&[Edit]>) -> SmolStr {
+        use crate::tokenizer::case_handling::align_chars;
+
+        let mapping = self.case_mapping();
+
+        match mutation {
+            CaseMutation::FirstCaps => mapping.upper_first(value),
+            CaseMutation::AllCaps => mapping.upper_case(value),
+            CaseMutation::Mixed(pattern) => {
+                let alignment = edits
+                    .and_then(|x| {
+                        let input = self.to_input_vec(pattern).strings;
+                        let output = self.to_input_vec(value).strings;
+                        let alignment = edit_alignment(input.len(), output.len(), x)?;
+                        Some(cluster_alignment(
+                            (pattern, &input),
+                            (value, &output),
+                            &alignment,
+                        ))
+                    })
+                    .unwrap_or_else(|| align_chars(pattern, value));
+                mapping.apply_case_pattern(pattern, value, &alignment)
+            }
+            CaseMutation::None => value.into(),
+        }
+    }
+
+    /// Splits `word` into mutator symbols, taking the longest symbol at each point. Symbols
+    /// may cover several grapheme clusters, as digraphs do, but never part of one, so that
+    /// no suggestion is left with half of a cluster.
+    fn to_input_vec(&self, word: &str) -> InputSymbols {
+        let key_table_len = self.mutator().alphabet().key_table().len();
+        let ends: Vec<usize> = GraphemeIndices::new(word)
+            .map(|(i, cluster)| i + cluster.len())
+            .collect();
+        let mut symbols = vec![];
+        let mut strings = vec![];
+        let mut virtual_symbols: Vec<SmolStr> = vec![];
+        let mut start = 0;
+
+        for &end in ends.iter() {
+            if end <= start {
+                continue;
+            }
+
+            let rest = &word[start..];
+            let is_end = |len: usize| ends.binary_search(&(start + len)).is_ok();
+
+            if let Some((symbol, len)) = self.input_symbols.longest_match_by(rest, is_end) {
+                symbols.push(symbol);
+                strings.push(SmolStr::from(&rest[..len]));
+                start += len;
+                continue;
+            }
+
+            // A cluster with no symbol of its own is split into symbols if all of its
+            // pieces have one, as in decomposed alphabets.
+            let cluster = &word[start..end];
+
+            if let Some(pieces) = self.input_symbols.split(cluster) {
+                let mut offset = 0;
+
+                for (symbol, len) in pieces.into_iter() {
+                    symbols.push(symbol);
+                    strings.push(SmolStr::from(&cluster[offset..offset + len]));
+                    offset += len;
+                }
+
+                start = end;
+                continue;
+            }
+
+            // Otherwise the whole cluster gets a symbol of its own, so that identity and
+            // unknown arcs can carry it through instead of it being dropped.
+            let s = SmolStr::from(cluster);
+            let index = match virtual_symbols.iter().position(|x| x == &s) {
+                Some(index) => index,
+                None => {
+                    virtual_symbols.push(s.clone());
+                    virtual_symbols.len() - 1
+                }
+            };
+
+            symbols.push((key_table_len + index) as SymbolNumber);
+            strings.push(s);
+            start = end;
+        }
+
+        InputSymbols {
+            symbols,
+            strings,
+            virtual_symbols,
+        }
     }
 
     #[allow(clippy::wrong_self_convention)]
     pub fn is_correct(self: Arc<Self>, word: &str) -> bool {
-        use crate::tokenizer::case_handling::*;
+        self.is_correct_with_config(word, &SpellerConfig::default())
+    }
+
+    /// Like `is_correct`, but follows `SpellerConfig::hyphen` for hyphenated words.
+    #[allow(clippy::wrong_self_convention)]
+    pub fn is_correct_with_config(self: Arc<Self>, word: &str, config: &SpellerConfig) -> bool {
+        let input = self.normalize_input(word);
+        let word = &*input.normalized.word;
+
+        if self.blocklist().is_rejected(word) {
+            return false;
+        }
+
+        if self.clone().is_correct_whole(word, config) {
+            return true;
+        }
+
+        match config.hyphen {
+            HyphenPolicy::Parts if word.contains('-') => word.split('-').all(|part| {
+                !self.blocklist().is_rejected(part) && self.clone().is_correct_whole(part, config)
+            }),
+            _ => false,
+        }
+    }
 
+    #[allow(clippy::wrong_self_convention)]
+    fn is_correct_whole(self: Arc<Self>, word: &str, config: &SpellerConfig) -> bool {
         if word.len() == 0 {
             return true;
         }
@@ -123,16 +830,17 @@ where
             return true;
         }
 
-        let words = word_variants(word).words;
+        let accept_config = accept_config(config);
+        let words = config.accept_variants(word, self.case_mapping());
 
         for word in words.into_iter() {
             let worker = SpellerWorker::new(
                 self.clone(),
                 self.to_input_vec(&word),
-                SpellerConfig::default(),
+                accept_config.clone(),
             );
 
-            if worker.is_correct() {
+            if worker.is_correct() || self.user_dictionary().contains_formed(&word) {
                 return true;
             }
         }
@@ -140,6 +848,39 @@ where
         false
     }
 
+    /// Runs the lexicon as an analyser over `word`, returning the output side of every
+    /// accepting path (e.g. lemma and tags), lowest weight first.
+    ///
+    /// Case variants are tried in the same order as `is_correct`, and the analyses of the
+    /// first variant that is accepted are returned.
+    pub fn analyze(self: Arc<Self>, word: &str) -> Vec<Analysis> {
+        if word.is_empty() {
+            return vec![];
+        }
+
+        let input = self.normalize_input(word);
+        let words = self
+            .case_mapping()
+            .word_variants(&input.normalized.word)
+            .words;
+
+        for word in words.into_iter() {
+            let worker = SpellerWorker::new(
+                self.clone(),
+                self.to_input_vec(&word),
+                SpellerConfig::default(),
+            );
+
+            let analyses = worker.analyze();
+
+            if !analyses.is_empty() {
+                return analyses;
+            }
+        }
+
+        vec![]
+    }
+
     pub fn suggest(self: Arc<Self>, word: &str) -> Vec<Suggestion> {
         self.suggest_with_config(word, &SpellerConfig::default())
     }
@@ -149,117 +890,710 @@ where
         word: &str,
         config: &SpellerConfig,
     ) -> Vec<Suggestion> {
-        use crate::tokenizer::case_handling::*;
+        self.suggest_with_result(word, config).suggestions
+    }
 
-        if word.len() == 0 {
-            return vec![];
+    /// Like `suggest_with_config`, but also reports whether the search was cut short by
+    /// `SpellerConfig::deadline` or `SpellerConfig::cancel`, in which case the suggestions
+    /// are the best found so far.
+    pub fn suggest_with_result(
+        self: Arc<Self>,
+        word: &str,
+        config: &SpellerConfig,
+    ) -> SuggestionResult {
+        if word.is_empty() {
+            return SuggestionResult::default();
+        }
+
+        let input = self.normalize_input(word);
+        let mut result = self
+            .clone()
+            .suggest_normalized(&input.normalized.word, config);
+
+        for sugg in result.suggestions.iter_mut() {
+            sugg.value = self.restore_output(&input, sugg.value());
+        }
+
+        result
+    }
+
+    /// `suggest_with_result` for a word that has been through `normalize_input`, with the
+    /// suggestions left for the caller to restore.
+    fn suggest_normalized(self: Arc<Self>, word: &str, config: &SpellerConfig) -> SuggestionResult {
+        let result = match config.suggest_variants(word, self.case_mapping()) {
+            Some(case_handler) => self.clone().suggest_case(case_handler, config),
+            None => self.clone().suggest_single(word, config),
+        };
+
+        match config.hyphen {
+            HyphenPolicy::Parts if word.contains('-') => {
+                self.suggest_hyphenated(word, config, result)
+            }
+            _ => result,
+        }
+    }
+
+    /// Adds suggestions that correct each part of a hyphenated word separately, with the
+    /// hyphens put back in place.
+    fn suggest_hyphenated(
+        self: Arc<Self>,
+        word: &str,
+        config: &SpellerConfig,
+        result: SuggestionResult,
+    ) -> SuggestionResult {
+        let SuggestionResult {
+            mut suggestions,
+            mut is_partial,
+        } = result;
+
+        // Partial words built so far, kept to the best `n_best` as parts are added. Weights
+        // only grow, so no candidate pruned here could have made it into the final list.
+        let mut candidates = vec![(String::new(), 0.0)];
+
+        for (index, part) in word.split('-').enumerate() {
+            let part_suggestions = if !self.blocklist().is_rejected(part)
+                && self.clone().is_correct_whole(part, config)
+            {
+                vec![Suggestion::new(part.into(), 0.0)]
+            } else {
+                let result = self.clone().suggest_normalized(part, config);
+                is_partial |= result.is_partial;
+                result.suggestions
+            };
+
+            let separator = if index == 0 { "" } else { "-" };
+            let mut next = Vec::with_capacity(candidates.len() * part_suggestions.len());
+
+            for (prefix, weight) in candidates.iter() {
+                for sugg in part_suggestions.iter() {
+                    next.push((
+                        format!("{}{}{}", prefix, separator, sugg.value()),
+                        weight + sugg.weight(),
+                    ));
+                }
+            }
+
+            next.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal));
+            if let Some(n_best) = config.n_best {
+                next.truncate(n_best);
+            }
+
+            candidates = next;
+        }
+
+        suggestions.extend(
+            candidates
+                .into_iter()
+                .filter(|(value, _)| value != word && !self.blocklist().is_blocked(value))
+                .map(|(value, weight)| Suggestion::new(value.into(), weight)),
+        );
+        suggestions.sort();
+
+        let mut seen = HashSet::new();
+        suggestions.retain(|x| seen.insert(x.value.clone()));
+
+        if let Some(n_best) = config.n_best {
+            suggestions.truncate(n_best);
+        }
+
+        SuggestionResult {
+            suggestions,
+            is_partial,
+        }
+    }
+
+    /// Suggests corrections for the current word of `context`, including splitting it in two
+    /// ("alot" to "a lot") and joining it with the word before or after it ("some thing" to
+    /// "something"). Joined suggestions say which neighbour they absorb in `Suggestion::merge`.
+    ///
+    /// If a language model is set, each suggestion's weight is interpolated with the model's
+    /// weight for the surrounding words with the suggestion in place.
+    pub fn suggest_in_context(
+        self: Arc<Self>,
+        context: &WordContext,
+        config: &SpellerConfig,
+    ) -> Vec<Suggestion> {
+        use crate::speller::suggestion::Merge;
+
+        let word = &context.current.1;
+        let mut candidates = vec![];
+
+        if !self.clone().is_correct_with_config(word, config) {
+            // Splitting between grapheme clusters keeps combining marks with their letters.
+            for (index, _) in GraphemeIndices::new(word).skip(1) {
+                let (left, right) = word.split_at(index);
+
+                if !left.chars().any(char::is_alphabetic) || !right.chars().any(char::is_alphabetic)
+                {
+                    continue;
+                }
+
+                let weights = self
+                    .clone()
+                    .acceptance_weight(left, config)
+                    .and_then(|x| Some(x + self.clone().acceptance_weight(right, config)?));
+
+                if let Some(weight) = weights {
+                    let value = format!("{} {}", left, right);
+
+                    if !self.blocklist().is_blocked(&value) {
+                        candidates
+                            .push(Suggestion::new(value.into(), weight + config.split_penalty));
+                    }
+                }
+            }
+        }
+
+        let neighbours = [
+            (context.first_before.as_ref(), Merge::Previous),
+            (context.first_after.as_ref(), Merge::Next),
+        ];
+
+        for (neighbour, merge) in neighbours.iter() {
+            let neighbour = match neighbour {
+                Some((_, neighbour)) => neighbour,
+                None => continue,
+            };
+
+            let value = match merge {
+                Merge::Previous => format!("{}{}", neighbour, word),
+                Merge::Next => format!("{}{}", word, neighbour),
+            };
+
+            if self.blocklist().is_blocked(&value) {
+                continue;
+            }
+
+            if let Some(weight) = self.clone().acceptance_weight(&value, config) {
+                let mut suggestion = Suggestion::new(value.into(), weight + config.merge_penalty);
+                suggestion.merge = Some(*merge);
+                candidates.push(suggestion);
+            }
+        }
+
+        let model = self
+            .language_model()
+            .filter(|_| config.language_model_weight > 0.0);
+        let beam = if model.is_some() { RERANK_BEAM } else { 1 };
+
+        // Truncating only once everything is in, the search has to find enough suggestions
+        // to make up for any of its own that the candidates push out.
+        let search_config = SpellerConfig {
+            n_best: config.n_best.map(|n| n * beam + candidates.len()),
+            ..config.clone()
+        };
+        let mut suggestions = self.clone().suggest_with_config(word, &search_config);
+        suggestions.append(&mut candidates);
+
+        if let Some(model) = model {
+            let lambda = config.language_model_weight;
+
+            for sugg in suggestions.iter_mut() {
+                let words = context_words(context, sugg);
+                let lm_weight = NgramModel::weight(model.sequence_log_prob(&words));
+                sugg.weight = (1.0 - lambda) * sugg.weight + lambda * lm_weight;
+            }
+        }
+
+        suggestions.sort();
+
+        let mut seen = HashSet::new();
+        suggestions.retain(|x| seen.insert(x.value.clone()));
+
+        if let Some(n_best) = config.n_best {
+            suggestions.truncate(n_best);
         }
 
-        if let Some(case_handling) = config.case_handling.as_ref() {
-            let case_handler = word_variants(word);
+        suggestions
+    }
+
+    /// The weight `word` is accepted at, following the same checks as
+    /// `is_correct_with_config`: the lightest path through the lexicon over the variants
+    /// `config` accepts, or the user dictionary's weight for a user word, summed over the
+    /// parts of a word accepted part by part. A word without letters weighs nothing. `None`
+    /// if the word is not accepted.
+    fn acceptance_weight(self: Arc<Self>, word: &str, config: &SpellerConfig) -> Option<Weight> {
+        let input = self.normalize_input(word);
+        let word = &*input.normalized.word;
+
+        if self.blocklist().is_rejected(word) {
+            return None;
+        }
 
-            self.suggest_case(case_handler, config, case_handling)
+        if let Some(weight) = self.clone().acceptance_weight_whole(word, config) {
+            return Some(weight);
+        }
+
+        match config.hyphen {
+            HyphenPolicy::Parts if word.contains('-') => word
+                .split('-')
+                .map(|part| {
+                    if self.blocklist().is_rejected(part) {
+                        None
+                    } else {
+                        self.clone().acceptance_weight_whole(part, config)
+                    }
+                })
+                .sum(),
+            _ => None,
+        }
+    }
+
+    /// Like `is_correct_whole`, but with the weight the word is accepted at.
+    fn acceptance_weight_whole(
+        self: Arc<Self>,
+        word: &str,
+        config: &SpellerConfig,
+    ) -> Option<Weight> {
+        if word.chars().all(|c| !GeneralCategory::of(c).is_letter()) {
+            return Some(0.0);
+        }
+
+        let accept_config = accept_config(config);
+        let words = config.accept_variants(word, self.case_mapping());
+        let weight = words
+            .iter()
+            .flat_map(|word| {
+                let worker = SpellerWorker::new(
+                    self.clone(),
+                    self.to_input_vec(word),
+                    accept_config.clone(),
+                );
+                worker.analyze()
+            })
+            .map(|x| x.weight)
+            .fold(None, |min: Option<Weight>, x| {
+                Some(min.map_or(x, |y| y.min(x)))
+            });
+
+        weight.or_else(|| {
+            let user_dictionary = self.user_dictionary();
+
+            if words.iter().any(|x| user_dictionary.contains_formed(x)) {
+                Some(user_dictionary.weight())
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Looks for a real-word error: a correctly spelled word that the language model finds
+    /// much less likely in `context` than another member of its confusion set. If so, the
+    /// likelier members are returned as suggestions, weighted by the language model.
+    ///
+    /// Returns `None` if the word is fine or there is no language model to judge by.
+    pub fn check_in_context(
+        self: Arc<Self>,
+        context: &WordContext,
+        config: &SpellerConfig,
+    ) -> Option<Vec<Suggestion>> {
+        let model = self.language_model()?;
+        let word = &context.current.1;
+        let alternatives = self.confusion_sets().alternatives(word);
+
+        if alternatives.is_empty() {
+            return None;
+        }
+
+        let log_prob = |value: &str| {
+            let words = context_words(context, &Suggestion::new(value.into(), 0.0));
+            model.sequence_log_prob(&words)
+        };
+
+        let current = log_prob(word);
+        let mapping = self.case_mapping();
+        let mutation = mapping.word_variants(word).mutation;
+        let blocklist = self.blocklist();
+
+        let mut suggestions: Vec<Suggestion> = alternatives
+            .iter()
+            .filter(|x| !blocklist.is_blocked(x))
+            .filter_map(|x| {
+                let alternative = log_prob(x);
+
+                if alternative - current >= config.confusion_threshold {
+                    Some(Suggestion::new(
+                        self.recase(&mutation, x, None),
+                        NgramModel::weight(alternative),
+                    ))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        if suggestions.is_empty() {
+            return None;
+        }
+
+        suggestions.sort();
+
+        if let Some(n_best) = config.n_best {
+            suggestions.truncate(n_best);
+        }
+
+        Some(suggestions)
+    }
+
+    /// Checks every word, suggesting corrections for the incorrect ones, in the order of
+    /// `words`. With the `parallel` feature, the words are spread across the rayon thread
+    /// pool.
+    pub fn check_batch(
+        self: Arc<Self>,
+        words: &[&str],
+        config: &SpellerConfig,
+    ) -> Vec<(bool, Vec<Suggestion>)>
+    where
+        Self: Send + Sync,
+    {
+        #[cfg(feature = "parallel")]
+        use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+        #[cfg(feature = "parallel")]
+        let words = words.par_iter();
+        #[cfg(not(feature = "parallel"))]
+        let words = words.iter();
+
+        words
+            .map(|word| self.clone().check_word(word, config))
+            .collect()
+    }
+
+    fn check_word(self: Arc<Self>, word: &str, config: &SpellerConfig) -> (bool, Vec<Suggestion>) {
+        if self.clone().is_correct_with_config(word, config) {
+            (true, vec![])
         } else {
-            self.suggest_single(word, config)
+            (false, self.suggest_with_config(word, config))
         }
     }
 
-    fn suggest_single(self: Arc<Self>, word: &str, config: &SpellerConfig) -> Vec<Suggestion> {
-        let worker = SpellerWorker::new(self.clone(), self.to_input_vec(word), config.clone());
+    /// Like `suggest_with_config`, but yields suggestions as the search finds them, in
+    /// nondecreasing weight order. The search only runs as far as the iterator is driven.
+    pub fn suggest_iter(
+        self: Arc<Self>,
+        word: &str,
+        config: &SpellerConfig,
+    ) -> SuggestionIter<F, T, U> {
+        let input = self.normalize_input(word);
+        let word = input.normalized.word.clone();
+        let word = &*word;
+
+        let (case, case_handling) = match config.suggest_variants(word, self.case_mapping()) {
+            Some(case) if !word.is_empty() => (case, Some(config.case_penalties())),
+            _ => {
+                let words = if word.is_empty() {
+                    vec![]
+                } else {
+                    vec![word.into()]
+                };
+
+                let case = CaseHandler {
+                    mutation: CaseMutation::None,
+                    mode: CaseMode::FirstResults,
+                    words,
+                };
 
-        worker.suggest()
+                (case, None)
+            }
+        };
+
+        SuggestionIter::new(self, case, case_handling, input, config.clone())
+    }
+
+    fn suggest_single(self: Arc<Self>, word: &str, config: &SpellerConfig) -> SuggestionResult {
+        self.suggestion_search(word, config).finish()
+    }
+
+    /// Sets up the search for a single case variant, covering both the lexicon and the user
+    /// dictionary.
+    fn suggestion_search(
+        self: Arc<Self>,
+        word: &str,
+        config: &SpellerConfig,
+    ) -> SuggestionSearch<F, T, U> {
+        let input = self.to_input_vec(word);
+        let user_suggestions =
+            self.user_dictionary()
+                .suggest(self.mutator(), &input.symbols, &input.strings, config);
+
+        let worker = SpellerWorker::new(self.clone(), input, config.clone());
+        SuggestionSearch::new(worker, user_suggestions)
     }
 
     fn suggest_case(
         self: Arc<Self>,
         case: CaseHandler,
         config: &SpellerConfig,
-        case_handling: &CaseHandlingConfig,
-    ) -> Vec<Suggestion> {
-        use crate::tokenizer::case_handling::*;
-        use crate::tokenizer::case_handling::{CaseMode, CaseMutation};
-
+    ) -> SuggestionResult {
         let CaseHandler {
             mutation,
             mode,
             words,
         } = case;
-        let mut best: HashMap<SmolStr, f32> = HashMap::new();
+        let case_handling = config.case_penalties();
+        let mut best: HashMap<SmolStr, Suggestion> = HashMap::new();
+        let mut is_partial = false;
 
         for word in words.iter() {
-            let worker = SpellerWorker::new(self.clone(), self.to_input_vec(&word), config.clone());
-            let mut suggestions = worker.suggest();
-
-            match mutation {
-                CaseMutation::FirstCaps => {
-                    suggestions.iter_mut().for_each(|x| {
-                        x.value = upper_first(x.value());
-                    });
-                }
-                CaseMutation::AllCaps => {
-                    suggestions.iter_mut().for_each(|x| {
-                        x.value = upper_case(x.value());
-                    });
-                }
-                _ => {}
+            if config.is_interrupted() {
+                is_partial = true;
+                break;
             }
 
+            let result = self.clone().suggestion_search(word, config).finish();
+            let mut suggestions = result.suggestions;
+            is_partial |= result.is_partial;
+
+            suggestions.iter_mut().for_each(|x| {
+                x.value = self.recase(&mutation, x.value(), x.edits());
+            });
+
             match mode {
-                CaseMode::MergeAll => {
+                CaseMode::MergeAll | CaseMode::MergeUnpenalized => {
                     for sugg in suggestions.into_iter() {
-                        let penalty_start =
-                            if !sugg.value().starts_with(word.chars().next().unwrap()) {
-                                case_handling.start_penalty
-                            } else {
-                                0.0
-                            };
-                        let penalty_end =
-                            if !sugg.value().ends_with(word.chars().rev().next().unwrap()) {
-                                case_handling.end_penalty
-                            } else {
-                                0.0
-                            };
-
-                        let distance =
-                            strsim::damerau_levenshtein(&words[0].as_str(), &word.as_str())
-                                + strsim::damerau_levenshtein(&word.as_str(), sugg.value());
-                        let penalty_middle = case_handling.mid_penalty * distance as f32;
-                        let additional_weight = penalty_start + penalty_end + penalty_middle;
-
-                        best.entry(sugg.value.clone())
-                            .and_modify(|entry| {
-                                let weight = sugg.weight + additional_weight;
-                                if entry as &_ > &weight {
-                                    *entry = weight
-                                }
-                            })
-                            .or_insert(sugg.weight + additional_weight);
+                        let weight = match mode {
+                            CaseMode::MergeAll => {
+                                sugg.weight
+                                    + case_penalty(&case_handling, &words[0], word, sugg.value())
+                            }
+                            _ => sugg.weight,
+                        };
+
+                        match best.get_mut(&sugg.value) {
+                            Some(entry) if entry.weight > weight => {
+                                *entry = Suggestion { weight, ..sugg };
+                            }
+                            Some(_) => {}
+                            None => {
+                                best.insert(sugg.value.clone(), Suggestion { weight, ..sugg });
+                            }
+                        }
                     }
                 }
                 CaseMode::FirstResults => {
                     if !suggestions.is_empty() {
-                        return suggestions;
+                        return SuggestionResult {
+                            suggestions,
+                            is_partial,
+                        };
                     }
                 }
             }
         }
 
-        if best.is_empty() {
-            return vec![];
-        }
-
-        let mut out = best
-            .into_iter()
-            .map(|(k, v)| Suggestion {
-                value: k,
-                weight: v,
-            })
-            .collect::<Vec<_>>();
+        let mut out = best.into_iter().map(|x| x.1).collect::<Vec<_>>();
         out.sort();
         if let Some(n_best) = config.n_best {
             out.truncate(n_best);
         }
-        out
+
+        SuggestionResult {
+            suggestions: out,
+            is_partial,
+        }
+    }
+}
+
+/// A word as looked up, and what to undo on its suggestions.
+struct NormalizedInput {
+    normalized: Normalized,
+    form: Option<NormalizationForm>,
+}
+
+struct VariantSearch<F: crate::vfs::File, T: Transducer<F>, U: Transducer<F>> {
+    word: SmolStr,
+    search: SuggestionSearch<F, T, U>,
+    /// The weight of the last suggestion found, below which this search has nothing left.
+    bound: Weight,
+    is_done: bool,
+}
+
+/// Suggestions for a word, found lazily in nondecreasing weight order. Created by
+/// `Speller::suggest_iter`.
+///
+/// When the suggestions of several case variants are merged, a suggestion is only yielded
+/// once none of the variants can still find a lighter one. Analyses reached after a
+/// suggestion has been yielded are not attached to it.
+pub struct SuggestionIter<F: crate::vfs::File, T: Transducer<F>, U: Transducer<F>> {
+    speller: Arc<Speller<F, T, U>>,
+    config: SpellerConfig,
+    case_handling: Option<CaseHandlingConfig>,
+    mutation: CaseMutation,
+    mode: CaseMode,
+    words: Vec<SmolStr>,
+    next_word: usize,
+    searches: Vec<VariantSearch<F, T, U>>,
+    pending: BinaryHeap<Reverse<Suggestion>>,
+    yielded: HashSet<SmolStr>,
+    input: NormalizedInput,
+    is_partial: bool,
+}
+
+impl<F, T, U> SuggestionIter<F, T, U>
+where
+    F: crate::vfs::File,
+    T: Transducer<F>,
+    U: Transducer<F>,
+{
+    fn new(
+        speller: Arc<Speller<F, T, U>>,
+        case: CaseHandler,
+        case_handling: Option<CaseHandlingConfig>,
+        input: NormalizedInput,
+        config: SpellerConfig,
+    ) -> SuggestionIter<F, T, U> {
+        SuggestionIter {
+            speller,
+            config,
+            case_handling,
+            mutation: case.mutation,
+            mode: case.mode,
+            words: case.words,
+            next_word: 0,
+            searches: vec![],
+            pending: BinaryHeap::new(),
+            yielded: HashSet::new(),
+            input,
+            is_partial: false,
+        }
+    }
+
+    /// Whether the search was cut short by `SpellerConfig::deadline` or
+    /// `SpellerConfig::cancel`.
+    pub fn is_partial(&self) -> bool {
+        self.is_partial
+    }
+
+    fn start_search(&mut self) -> bool {
+        let word = match self.words.get(self.next_word) {
+            Some(word) => word.clone(),
+            None => return false,
+        };
+        self.next_word += 1;
+
+        let search = self.speller.clone().suggestion_search(&word, &self.config);
+
+        self.searches.push(VariantSearch {
+            word,
+            search,
+            bound: f32::NEG_INFINITY,
+            is_done: false,
+        });
+
+        true
+    }
+
+    fn next_first_results(&mut self) -> Option<Suggestion> {
+        loop {
+            if self.searches.last().map(|x| x.is_done).unwrap_or(true) {
+                // Later variants are only tried while earlier ones have found nothing.
+                if !self.yielded.is_empty() || !self.start_search() {
+                    return None;
+                }
+            }
+
+            let variant = self.searches.last_mut().unwrap();
+
+            match variant.search.next() {
+                Some(mut sugg) => {
+                    sugg.value = self
+                        .speller
+                        .recase(&self.mutation, sugg.value(), sugg.edits());
+                    self.yielded.insert(sugg.value.clone());
+                    return Some(sugg);
+                }
+                None => {
+                    variant.is_done = true;
+                    self.is_partial |= variant.search.is_partial();
+                }
+            }
+        }
+    }
+
+    fn next_merged(&mut self) -> Option<Suggestion> {
+        if self.searches.is_empty() {
+            while self.start_search() {}
+        }
+
+        loop {
+            let bound = self
+                .searches
+                .iter()
+                .filter(|x| !x.is_done)
+                .map(|x| x.bound)
+                .fold(f32::INFINITY, f32::min);
+
+            if let Some(Reverse(sugg)) = self.pending.peek() {
+                if sugg.weight <= bound {
+                    let Reverse(sugg) = self.pending.pop().unwrap();
+
+                    if self.yielded.insert(sugg.value.clone()) {
+                        return Some(sugg);
+                    }
+
+                    continue;
+                }
+            }
+
+            let variant = self
+                .searches
+                .iter_mut()
+                .filter(|x| !x.is_done)
+                .min_by(|a, b| a.bound.partial_cmp(&b.bound).unwrap_or(Ordering::Equal))?;
+
+            match variant.search.next() {
+                Some(mut sugg) => {
+                    variant.bound = sugg.weight;
+                    sugg.value = self
+                        .speller
+                        .recase(&self.mutation, sugg.value(), sugg.edits());
+
+                    if let (CaseMode::MergeAll, Some(case_handling)) =
+                        (self.mode, self.case_handling.as_ref())
+                    {
+                        sugg.weight += case_penalty(
+                            case_handling,
+                            &self.words[0],
+                            &variant.word,
+                            sugg.value(),
+                        );
+                    }
+
+                    self.pending.push(Reverse(sugg));
+                }
+                None => {
+                    variant.is_done = true;
+                    self.is_partial |= variant.search.is_partial();
+                }
+            }
+        }
+    }
+}
+
+impl<F, T, U> Iterator for SuggestionIter<F, T, U>
+where
+    F: crate::vfs::File,
+    T: Transducer<F>,
+    U: Transducer<F>,
+{
+    type Item = Suggestion;
+
+    fn next(&mut self) -> Option<Suggestion> {
+        if let Some(n_best) = self.config.n_best {
+            if self.yielded.len() >= n_best {
+                return None;
+            }
+        }
+
+        let sugg = match self.mode {
+            CaseMode::FirstResults => self.next_first_results(),
+            CaseMode::MergeAll | CaseMode::MergeUnpenalized => self.next_merged(),
+        };
+
+        sugg.map(|x| Suggestion {
+            value: self.speller.restore_output(&self.input, x.value()),
+            ..x
+        })
     }
 }
 
@@ -350,6 +1684,7 @@ pub(crate) mod ffi {
                     start_penalty: c.start_penalty,
                     end_penalty: c.end_penalty,
                     mid_penalty: c.mid_penalty,
+                    ..CaseHandlingConfig::default()
                 })
             };
 
@@ -371,6 +1706,7 @@ pub(crate) mod ffi {
                 },
                 case_handling,
                 node_pool_size: config.node_pool_size,
+                ..SpellerConfig::default()
             };
 
             Ok(out)
@@ -475,3 +1811,422 @@ pub(crate) mod ffi {
         suggestions[index].value().to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::speller::normalizer::NormalizerRule;
+    use crate::speller::testing::speller;
+
+    fn values(suggestions: &[Suggestion]) -> Vec<&str> {
+        suggestions.iter().map(|x| x.value()).collect()
+    }
+
+    #[test]
+    fn check_batch_keeps_order() {
+        let speller = speller(&[("abc", 0.0), ("abd", 1.0)]);
+        let words = ["abd", "acd", "abc", "ab", "abd"];
+        let results = speller
+            .clone()
+            .check_batch(&words, &SpellerConfig::default());
+        let results: Vec<(bool, Vec<&str>)> = results.iter().map(|x| (x.0, values(&x.1))).collect();
+
+        assert_eq!(
+            results,
+            vec![
+                (true, vec![]),
+                (false, vec!["abd"]),
+                (true, vec![]),
+                (false, vec!["abc", "abd"]),
+                (true, vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn splits_and_merges_in_context() {
+        let speller = speller(&[("ab", 1.0), ("cd", 2.0), ("abce", 0.0)]);
+        let config = SpellerConfig {
+            split_penalty: 0.5,
+            merge_penalty: 0.25,
+            n_best: Some(2),
+            case_handling: None,
+            ..SpellerConfig::default()
+        };
+        let context = |current: &str, after: Option<&str>| WordContext {
+            current: (0, current.into()),
+            first_before: None,
+            second_before: None,
+            first_after: after.map(|x| (0, x.into())),
+            second_after: None,
+        };
+
+        // The split weighs what its parts do, plus the penalty.
+        let suggestions = speller
+            .clone()
+            .suggest_in_context(&context("abcd", None), &config);
+        assert_eq!(values(&suggestions), vec!["abce", "ab cd"]);
+        assert_eq!(suggestions[0].weight, 1.0);
+        assert_eq!(suggestions[1].weight, 3.5);
+
+        // The merge is lighter than the same word found by the search, and replaces it.
+        let suggestions = speller
+            .clone()
+            .suggest_in_context(&context("abc", Some("e")), &config);
+        assert_eq!(values(&suggestions), vec!["abce", "ab"]);
+        assert_eq!(suggestions[0].weight, 0.25);
+        assert_eq!(
+            suggestions[0].merge,
+            Some(crate::speller::suggestion::Merge::Next)
+        );
+
+        // Nothing is cut before the candidates are in: the one place left goes to the
+        // lightest of them all.
+        let config = SpellerConfig {
+            n_best: Some(1),
+            split_penalty: 0.0,
+            ..config
+        };
+        let speller = crate::speller::testing::speller(&[("ab", 0.0), ("cd", 0.0), ("abce", 0.0)]);
+        let suggestions = speller.suggest_in_context(&context("abcd", None), &config);
+        assert_eq!(values(&suggestions), vec!["ab cd"]);
+    }
+
+    #[test]
+    fn combining_sequences() {
+        // A cluster the mutator has no symbols for is one symbol, not a letter and a mark.
+        let input = speller(&[("abc", 0.0)]).to_input_vec("abe\u{301}");
+        assert_eq!(input.strings, vec!["a", "b", "e\u{301}"]);
+        assert_eq!(input.virtual_symbols, vec!["e\u{301}"]);
+
+        // Words are split between clusters only, even where a split inside one would give
+        // two known words.
+        let speller = speller(&[
+            ("abe\u{301}", 0.0),
+            ("abe", 0.0),
+            ("cd", 0.0),
+            ("\u{301}cd", 0.0),
+        ]);
+        let config = SpellerConfig {
+            case_handling: None,
+            ..SpellerConfig::default()
+        };
+        let context = WordContext {
+            current: (0, "abe\u{301}cd".into()),
+            first_before: None,
+            second_before: None,
+            first_after: None,
+            second_after: None,
+        };
+        let suggestions = speller.suggest_in_context(&context, &config);
+        assert!(values(&suggestions).contains(&"abe\u{301} cd"));
+        assert!(!values(&suggestions).contains(&"abe \u{301}cd"));
+    }
+
+    #[test]
+    fn reranks_beyond_n_best() {
+        let speller = speller(&[("abc", 0.0), ("abd", 3.0)]);
+        let config = SpellerConfig {
+            n_best: Some(1),
+            ..SpellerConfig::default()
+        };
+        let context = WordContext {
+            current: (0, "ab".into()),
+            first_before: None,
+            second_before: None,
+            first_after: None,
+            second_after: None,
+        };
+
+        let suggestions = speller.clone().suggest_in_context(&context, &config);
+        assert_eq!(values(&suggestions), vec!["abc"]);
+
+        // The model prefers a suggestion the search alone ranks below the cut.
+        let arpa = "\\data\\\nngram 1=2\n\n\\1-grams:\n-5.0\tabc\n-0.5\tabd\n\n\\end\\\n";
+        speller.set_language_model(Some(NgramModel::from_arpa(arpa.as_bytes()).unwrap()));
+        let suggestions = speller.suggest_in_context(&context, &config);
+        assert_eq!(values(&suggestions), vec!["abd"]);
+    }
+
+    #[test]
+    fn check_in_context() {
+        let speller = speller(&[("abc", 0.0), ("abd", 0.0), ("x", 0.0)]);
+        let config = SpellerConfig::default();
+        let context = |current: &str| WordContext {
+            current: (0, current.into()),
+            first_before: Some((0, "x".into())),
+            second_before: None,
+            first_after: None,
+            second_after: None,
+        };
+
+        speller.confusion_sets_mut().insert(&["abc", "abd"]);
+        assert!(speller
+            .clone()
+            .check_in_context(&context("abc"), &config)
+            .is_none());
+
+        let arpa = concat!(
+            "\\data\\\nngram 1=3\nngram 2=1\n\n",
+            "\\1-grams:\n-1.0\tx\t0.0\n-3.0\tabc\n-1.0\tabd\n\n",
+            "\\2-grams:\n-0.1\tx abd\n\n",
+            "\\end\\\n",
+        );
+        speller.set_language_model(Some(NgramModel::from_arpa(arpa.as_bytes()).unwrap()));
+
+        let suggestions = speller.clone().check_in_context(&context("Abc"), &config);
+        assert_eq!(values(&suggestions.unwrap()), vec!["Abd"]);
+        assert!(speller
+            .clone()
+            .check_in_context(&context("abd"), &config)
+            .is_none());
+        assert!(speller.check_in_context(&context("x"), &config).is_none());
+    }
+
+    #[test]
+    fn reject_lowercase_only() {
+        let speller = speller(&[("abc", 0.0), ("Abd", 0.0)]);
+        let config = SpellerConfig {
+            case_handling: Some(CaseHandlingConfig {
+                reject_lowercase_only: true,
+                ..CaseHandlingConfig::default()
+            }),
+            ..SpellerConfig::default()
+        };
+
+        assert!(speller.clone().is_correct_with_config("abc", &config));
+        assert!(!speller.clone().is_correct_with_config("Abc", &config));
+        assert!(!speller.clone().is_correct_with_config("aBc", &config));
+        assert!(speller.clone().is_correct_with_config("ABC", &config));
+        assert!(speller.clone().is_correct_with_config("Abd", &config));
+        assert!(speller.is_correct_with_config("ABD", &config));
+    }
+
+    #[test]
+    fn recase_multichar_symbols() {
+        use crate::transducer::testing::TransducerBuilder;
+
+        // The lexicon has "ADZ" and the error model may delete a final "E", with "DZ" a
+        // symbol of its own in both.
+        let mut lexicon = TransducerBuilder::new();
+        let (a, dz) = (lexicon.state(), lexicon.state());
+        lexicon
+            .arc(0, "A", "A", a, 0.0)
+            .arc(a, "DZ", "DZ", dz, 0.0)
+            .final_state(dz, 0.0);
+
+        let mut mutator = TransducerBuilder::new();
+        let edited = mutator.state();
+        mutator
+            .arc(0, "A", "A", 0, 0.0)
+            .arc(0, "DZ", "DZ", 0, 0.0)
+            .arc(0, "E", "", edited, 1.0)
+            .final_state(0, 0.0)
+            .final_state(edited, 0.0);
+
+        let speller = Speller::new(mutator.build(), lexicon.build());
+        let config = SpellerConfig {
+            with_edits: true,
+            ..SpellerConfig::default()
+        };
+
+        // The deleted "e" is the fourth letter but the third symbol.
+        let suggestions = speller.suggest_with_config("aDZe", &config);
+        assert_eq!(values(&suggestions), vec!["aDZ"]);
+    }
+
+    #[test]
+    fn acceptance_weights() {
+        let speller = speller(&[("ab", 1.0), ("cd", 2.0)]);
+        let config = SpellerConfig {
+            hyphen: HyphenPolicy::Parts,
+            ..SpellerConfig::default()
+        };
+
+        assert_eq!(
+            speller.clone().acceptance_weight("ab-cd", &config),
+            Some(3.0)
+        );
+        assert_eq!(
+            speller
+                .clone()
+                .acceptance_weight("ab-cd", &SpellerConfig::default()),
+            None
+        );
+        assert_eq!(speller.clone().acceptance_weight("12", &config), Some(0.0));
+        assert_eq!(speller.acceptance_weight("ac", &config), None);
+
+        // Words accepted without their diacritics weigh what the word with them does.
+        let speller = Speller::new(
+            crate::transducer::testing::edit_distance("abá", 1.0),
+            crate::transducer::testing::lexicon(&[("áb", 2.0)], false),
+        );
+        let config = SpellerConfig {
+            accept_without_diacritics: true,
+            ..SpellerConfig::default()
+        };
+
+        assert_eq!(speller.clone().acceptance_weight("ab", &config), Some(2.0));
+        assert_eq!(
+            speller.acceptance_weight("ab", &SpellerConfig::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn acceptance_ignores_search_limits() {
+        let speller = speller(&[("abc", 5.0)]);
+        let config = SpellerConfig {
+            max_weight: Some(1.0),
+            n_best: Some(0),
+            ..SpellerConfig::default()
+        };
+
+        assert!(speller.is_correct_with_config("abc", &config));
+    }
+
+    #[test]
+    fn hyphenated_parts() {
+        let speller = speller(&[("ab", 0.0), ("ccd", 0.0)]);
+        let config = SpellerConfig {
+            hyphen: HyphenPolicy::Parts,
+            ..SpellerConfig::default()
+        };
+
+        assert!(speller.clone().is_correct_with_config("ab-ccd", &config));
+        assert!(!speller.clone().is_correct("ab-ccd"));
+        assert!(!speller.clone().is_correct_with_config("ab-ccx", &config));
+
+        // Each part is normalized once, with the word, and restored once.
+        speller.normalizer_mut().push(NormalizerRule::Replace {
+            from: "c".into(),
+            to: "cc".into(),
+        });
+        assert!(speller.clone().is_correct_with_config("ab-cd", &config));
+
+        let suggestions = speller.clone().suggest_with_config("ab-ca", &config);
+        assert_eq!(values(&suggestions), vec!["ab-cd"]);
+        assert!(speller.suggest("ab-ca").is_empty());
+    }
+
+    #[test]
+    fn missing_diacritics() {
+        use crate::transducer::testing::{edit_distance, lexicon};
+
+        let speller = Speller::new(
+            edit_distance("acčáhpes", 1.0),
+            lexicon(&[("čáhppes", 0.0)], false),
+        );
+
+        // Without the distance penalties of case handling, each restored letter weighs
+        // `diacritic_weight`. The error model alone allows only one edit.
+        let config = SpellerConfig {
+            case_handling: Some(CaseHandlingConfig {
+                start_penalty: 0.0,
+                end_penalty: 0.0,
+                mid_penalty: 0.0,
+                ..CaseHandlingConfig::default()
+            }),
+            ..SpellerConfig::default()
+        };
+        assert!(speller
+            .clone()
+            .suggest_with_config("cahppes", &config)
+            .is_empty());
+
+        let config = SpellerConfig {
+            diacritic_weight: Some(0.5),
+            ..config
+        };
+        let suggestions = speller.clone().suggest_with_config("cahppes", &config);
+        assert_eq!(values(&suggestions), vec!["čáhppes"]);
+        assert_eq!(suggestions[0].weight(), 1.0);
+        let suggestions = speller.clone().suggest_with_config("čahppes", &config);
+        assert_eq!(values(&suggestions), vec!["čáhppes"]);
+        assert_eq!(suggestions[0].weight(), 0.5);
+
+        let config = SpellerConfig {
+            accept_without_diacritics: true,
+            ..SpellerConfig::default()
+        };
+        assert!(speller.clone().is_correct_with_config("cahppes", &config));
+        assert!(!speller.clone().is_correct("cahppes"));
+        assert!(!speller.is_correct_with_config("cahpes", &config));
+    }
+
+    #[test]
+    fn decomposed_input() {
+        let speller = speller(&[("čáhppes", 0.0)]);
+        assert_eq!(speller.normalization(), Some(NormalizationForm::Nfc));
+
+        // Decomposed input is looked up precomposed, at the same weight, and answered
+        // decomposed.
+        let suggestions = speller.clone().suggest("c\u{30c}a\u{301}hpes");
+        let precomposed = speller.clone().suggest("čáhpes");
+        assert_eq!(values(&suggestions), vec!["c\u{30c}a\u{301}hppes"]);
+        assert_eq!(values(&precomposed), vec!["čáhppes"]);
+        assert_eq!(suggestions[0].weight(), precomposed[0].weight());
+
+        assert!(speller.clone().is_correct("c\u{30c}a\u{301}hppes"));
+        assert!(speller.clone().is_correct("čáhppes"));
+        assert!(!speller.is_correct("c\u{30c}a\u{301}hpes"));
+    }
+
+    #[test]
+    fn blocklist_in_word_form() {
+        let speller = speller(&[("ab", 0.0), ("ccd", 0.0), ("ccb", 1.0)]);
+
+        // Entries are written as the speller looks words up, even those added before the
+        // normalizer was set.
+        speller.blocklist_mut().insert("cd", true);
+        speller.normalizer_mut().push(NormalizerRule::Replace {
+            from: "c".into(),
+            to: "cc".into(),
+        });
+        assert!(!speller.clone().is_correct("cd"));
+        assert!(!speller.clone().is_correct("ccd"));
+        assert_eq!(values(&speller.clone().suggest("ca")), vec!["cb"]);
+
+        let config = SpellerConfig {
+            hyphen: HyphenPolicy::Parts,
+            ..SpellerConfig::default()
+        };
+        assert!(!speller.clone().is_correct_with_config("ab-cd", &config));
+        assert!(speller.blocklist_mut().remove("cd"));
+        assert!(speller.clone().is_correct_with_config("ab-cd", &config));
+    }
+
+    #[test]
+    fn suggestion_iter() {
+        let speller = speller(&[("abc", 0.0), ("abd", 2.0), ("abe", 1.0), ("Abf", 0.0)]);
+
+        let config = SpellerConfig::default();
+        let mut iter = speller.clone().suggest_iter("ab", &config);
+        let suggestions: Vec<Suggestion> = iter.by_ref().collect();
+        assert_eq!(values(&suggestions), vec!["abc", "abe", "abd"]);
+        assert!(iter.next().is_none());
+        assert!(!iter.is_partial());
+
+        // Merging: "Ab" and "ab" are searched together, and what each finds is interleaved
+        // by weight, case penalties included.
+        let suggestions: Vec<Suggestion> = speller.clone().suggest_iter("Ab", &config).collect();
+        assert!(suggestions.windows(2).all(|x| x[0].weight <= x[1].weight));
+        let mut expected = speller.clone().suggest_with_config("Ab", &config);
+        expected.sort();
+        assert_eq!(values(&suggestions), values(&expected));
+        assert_eq!(suggestions.len(), 4);
+
+        // First results: "ab" is only searched if "Ab" finds nothing.
+        let config = SpellerConfig {
+            case_handling: Some(CaseHandlingConfig {
+                mode: Some(CaseMode::FirstResults),
+                ..CaseHandlingConfig::default()
+            }),
+            ..SpellerConfig::default()
+        };
+        let suggestions: Vec<Suggestion> = speller.clone().suggest_iter("Ab", &config).collect();
+        assert_eq!(values(&suggestions), vec!["Abf"]);
+        let suggestions: Vec<Suggestion> = speller.clone().suggest_iter("Ac", &config).collect();
+        assert_eq!(values(&suggestions), vec!["Abc"]);
+    }
+}
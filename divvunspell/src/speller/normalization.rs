@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+use unic_normal::StrNormalForm;
+use unic_ucd_category::GeneralCategory;
+
+use crate::types::SymbolNumber;
+
+/// A Unicode normalization form. Lexicons are built in one of them, while input may come
+/// in either, as some keyboards send decomposed letters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NormalizationForm {
+    /// Precomposed letters, such as "á" as one character.
+    Nfc,
+    /// Decomposed letters, such as "á" as "a" followed by a combining acute accent.
+    Nfd,
+}
+
+impl NormalizationForm {
+    pub fn normalize(self, s: &str) -> SmolStr {
+        match self {
+            NormalizationForm::Nfc => s.nfc().collect::<String>().into(),
+            NormalizationForm::Nfd => s.nfd().collect::<String>().into(),
+        }
+    }
+
+    pub fn is_normalized(self, s: &str) -> bool {
+        self.normalize(s) == s
+    }
+
+    /// The form `s` is written in, if it is in exactly one of them. Text that is the same
+    /// in both, such as plain ASCII, gives `None`.
+    pub fn of(s: &str) -> Option<NormalizationForm> {
+        match (
+            NormalizationForm::Nfc.is_normalized(s),
+            NormalizationForm::Nfd.is_normalized(s),
+        ) {
+            (true, false) => Some(NormalizationForm::Nfc),
+            (false, true) => Some(NormalizationForm::Nfd),
+            _ => None,
+        }
+    }
+
+    /// Guesses the form of a transducer alphabet from its symbols: any precomposed letter
+    /// means NFC, otherwise any lone combining mark means NFD.
+    pub fn detect(key_table: &[SmolStr]) -> Option<NormalizationForm> {
+        let mut form = None;
+
+        for symbol in key_table.iter() {
+            if NormalizationForm::of(symbol) == Some(NormalizationForm::Nfc) {
+                return Some(NormalizationForm::Nfc);
+            }
+
+            let starts_with_mark = symbol
+                .chars()
+                .next()
+                .map(|c| GeneralCategory::of(c).is_mark())
+                .unwrap_or(false);
+
+            if starts_with_mark {
+                form = Some(NormalizationForm::Nfd);
+            }
+        }
+
+        form
+    }
+}
+
+/// `s` with its diacritics removed, by dropping combining marks after decomposition and
+/// mapping the few letters with a stroke or hook that do not decompose, e.g. "đ" to "d".
+pub fn strip_diacritics(s: &str) -> SmolStr {
+    s.nfd()
+        .filter(|c| !GeneralCategory::of(*c).is_mark())
+        .map(|c| match c {
+            'đ' => 'd',
+            'Đ' => 'D',
+            'ŧ' => 't',
+            'Ŧ' => 'T',
+            'ŋ' => 'n',
+            'Ŋ' => 'N',
+            'ø' => 'o',
+            'Ø' => 'O',
+            'ł' => 'l',
+            'Ł' => 'L',
+            _ => c,
+        })
+        .collect::<String>()
+        .into()
+}
+
+/// For each symbol of `from`, the symbols of `to` that are the same letter with other
+/// diacritics. Special symbols such as flags are left out.
+pub(crate) fn diacritic_variants(from: &[SmolStr], to: &[SmolStr]) -> Vec<Vec<SymbolNumber>> {
+    let is_special = |x: &SmolStr| x.is_empty() || (x.starts_with('@') && x.ends_with('@'));
+    let stripped: Vec<Option<SmolStr>> = to
+        .iter()
+        .map(|x| {
+            if is_special(x) {
+                None
+            } else {
+                Some(strip_diacritics(x))
+            }
+        })
+        .collect();
+
+    from.iter()
+        .map(|symbol| {
+            if is_special(symbol) {
+                return vec![];
+            }
+
+            let base = strip_diacritics(symbol);
+
+            stripped
+                .iter()
+                .enumerate()
+                .filter(|(i, x)| x.as_ref() == Some(&base) && !base.is_empty() && to[*i] != *symbol)
+                .map(|(i, _)| i as SymbolNumber)
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_and_convert() {
+        let nfc: Vec<SmolStr> = vec!["a".into(), "á".into(), "@_EPSILON_SYMBOL_@".into()];
+        let nfd: Vec<SmolStr> = vec!["a".into(), "\u{301}".into()];
+        let plain: Vec<SmolStr> = vec!["a".into(), "b".into()];
+
+        assert_eq!(
+            NormalizationForm::detect(&nfc),
+            Some(NormalizationForm::Nfc)
+        );
+        assert_eq!(
+            NormalizationForm::detect(&nfd),
+            Some(NormalizationForm::Nfd)
+        );
+        assert_eq!(NormalizationForm::detect(&plain), None);
+
+        assert_eq!(NormalizationForm::of("ča\u{301}"), None);
+        assert_eq!(
+            NormalizationForm::of("c\u{30c}a\u{301}"),
+            Some(NormalizationForm::Nfd)
+        );
+        assert_eq!(NormalizationForm::Nfc.normalize("c\u{30c}a\u{301}"), "čá");
+    }
+
+    #[test]
+    fn diacritics() {
+        assert_eq!(strip_diacritics("čáhppes"), "cahppes");
+        assert_eq!(strip_diacritics("ođđa"), "odda");
+
+        let table: Vec<SmolStr> = vec![
+            "@_EPSILON_SYMBOL_@".into(),
+            "c".into(),
+            "č".into(),
+            "a".into(),
+            "á".into(),
+            "C".into(),
+        ];
+        let variants = diacritic_variants(&table, &table);
+        assert!(variants[0].is_empty());
+        assert_eq!(variants[1], vec![2]);
+        assert_eq!(variants[4], vec![3]);
+        assert!(variants[5].is_empty());
+    }
+}
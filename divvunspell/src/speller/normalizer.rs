@@ -0,0 +1,166 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use smol_str::SmolStr;
+
+/// A transformation applied to words before they are looked up.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NormalizerRule {
+    /// Replaces `from` with `to`, e.g. a typographic apostrophe with a plain one, or a
+    /// ligature with its letters. Suggestions get `from` back in place of `to`.
+    Replace { from: SmolStr, to: SmolStr },
+    /// Collapses runs of `min` or more of the same character to one, e.g. "sooooo" to "so"
+    /// when `min` is 3. Suggestions are left collapsed.
+    CollapseRepeats {
+        #[serde(deserialize_with = "deserialize_count")]
+        min: usize,
+    },
+}
+
+/// Reads a count written as a number, as in JSON, or as text, as in XML attributes.
+fn deserialize_count<'de, D: Deserializer<'de>>(deserializer: D) -> Result<usize, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Count {
+        Number(usize),
+        Text(String),
+    }
+
+    match Count::deserialize(deserializer)? {
+        Count::Number(x) => Ok(x),
+        Count::Text(x) => x.parse().map_err(serde::de::Error::custom),
+    }
+}
+
+impl NormalizerRule {
+    fn apply(&self, word: &str) -> Option<SmolStr> {
+        match self {
+            NormalizerRule::Replace { from, to } => {
+                if from.is_empty() || !word.contains(&**from) {
+                    return None;
+                }
+
+                Some(word.replace(&**from, to).into())
+            }
+            NormalizerRule::CollapseRepeats { min } => {
+                let min = (*min).max(2);
+                let chars: Vec<char> = word.chars().collect();
+                let mut out = String::with_capacity(word.len());
+                let mut i = 0;
+
+                while i < chars.len() {
+                    let run = chars[i..].iter().take_while(|x| **x == chars[i]).count();
+                    let keep = if run >= min { 1 } else { run };
+                    out.extend(&chars[i..i + keep]);
+                    i += run;
+                }
+
+                if out.len() == word.len() {
+                    None
+                } else {
+                    Some(out.into())
+                }
+            }
+        }
+    }
+
+    fn restore(&self, suggestion: &str) -> SmolStr {
+        match self {
+            NormalizerRule::Replace { from, to } if !to.is_empty() => {
+                suggestion.replace(&**to, from).into()
+            }
+            _ => suggestion.into(),
+        }
+    }
+}
+
+/// A word after `Normalizer::normalize`, remembering which rules changed it.
+#[derive(Debug, Clone)]
+pub struct Normalized {
+    pub word: SmolStr,
+    applied: Vec<usize>,
+}
+
+/// Rules run over words, in order, before lookup, and undone on the suggestions for them.
+/// Archives may declare rules in their metadata.
+#[derive(Debug, Clone, Default)]
+pub struct Normalizer {
+    rules: Vec<NormalizerRule>,
+}
+
+impl Normalizer {
+    pub fn new(rules: Vec<NormalizerRule>) -> Normalizer {
+        Normalizer { rules }
+    }
+
+    pub fn rules(&self) -> &[NormalizerRule] {
+        &self.rules
+    }
+
+    pub fn push(&mut self, rule: NormalizerRule) {
+        self.rules.push(rule);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn normalize(&self, word: &str) -> Normalized {
+        let mut normalized = Normalized {
+            word: word.into(),
+            applied: vec![],
+        };
+
+        for (index, rule) in self.rules.iter().enumerate() {
+            if let Some(word) = rule.apply(&normalized.word) {
+                normalized.word = word;
+                normalized.applied.push(index);
+            }
+        }
+
+        normalized
+    }
+
+    /// Undoes, in reverse order, the rules that changed the word a suggestion is for.
+    pub fn restore(&self, normalized: &Normalized, suggestion: &str) -> SmolStr {
+        normalized
+            .applied
+            .iter()
+            .rev()
+            .filter_map(|x| self.rules.get(*x))
+            .fold(SmolStr::from(suggestion), |value, rule| {
+                rule.restore(&value)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_and_restore() {
+        let normalizer = Normalizer::new(vec![
+            NormalizerRule::Replace {
+                from: "’".into(),
+                to: "'".into(),
+            },
+            NormalizerRule::Replace {
+                from: "ﬁ".into(),
+                to: "fi".into(),
+            },
+            NormalizerRule::CollapseRepeats { min: 3 },
+        ]);
+
+        let normalized = normalizer.normalize("don’t");
+        assert_eq!(normalized.word, "don't");
+        assert_eq!(normalizer.restore(&normalized, "doesn't"), "doesn’t");
+
+        let normalized = normalizer.normalize("sooooo");
+        assert_eq!(normalized.word, "so");
+        assert_eq!(normalizer.restore(&normalized, "so"), "so");
+
+        let normalized = normalizer.normalize("book");
+        assert_eq!(normalized.word, "book");
+        assert_eq!(normalizer.restore(&normalized, "boo'k"), "boo'k");
+    }
+}
@@ -0,0 +1,215 @@
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+
+use crate::speller::suggestion::Suggestion;
+use crate::tokenizer::case_handling::CaseMapping;
+use crate::types::Weight;
+
+/// Learns which suggestions a user picks and moves them up in later results.
+///
+/// Each time a suggestion is chosen for an input, it gets lighter for that input by
+/// `bonus`, and for similar inputs, those one edit away, by `bonus * similar_factor`, up to
+/// `max_bonus` in total. Inputs and suggestions are compared without regard to case, as
+/// `case_mapping` writes it, which should be the speller's. The learned state is plain data,
+/// so it can be saved with serde and restored later.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reranker {
+    pub bonus: Weight,
+    pub similar_factor: Weight,
+    pub max_bonus: Weight,
+    #[serde(default = "default_case_mapping")]
+    pub case_mapping: CaseMapping,
+    choices: HashMap<SmolStr, HashMap<SmolStr, u32>>,
+}
+
+fn default_case_mapping() -> CaseMapping {
+    CaseMapping::Default
+}
+
+impl Default for Reranker {
+    fn default() -> Reranker {
+        Reranker::new()
+    }
+}
+
+impl Reranker {
+    pub fn new() -> Reranker {
+        Reranker {
+            bonus: 2.0,
+            similar_factor: 0.25,
+            max_bonus: 10.0,
+            case_mapping: CaseMapping::Default,
+            choices: HashMap::new(),
+        }
+    }
+
+    /// Records that `chosen` was picked as the correction of `input`.
+    pub fn record(&mut self, input: &str, chosen: &str) {
+        let input = self.case_mapping.lower_case(input);
+        let chosen = self.case_mapping.lower_case(chosen);
+
+        // Counts saturate, so that no saved or hand-edited state can overflow them.
+        let count = self
+            .choices
+            .entry(input)
+            .or_default()
+            .entry(chosen)
+            .or_default();
+        *count = count.saturating_add(1);
+    }
+
+    /// Lowers the weights of previously chosen suggestions for `input`, then sorts and
+    /// truncates to `n_best`. Pass more candidates than `n_best` to let learned choices
+    /// move in from further down.
+    pub fn apply(
+        &self,
+        input: &str,
+        mut suggestions: Vec<Suggestion>,
+        n_best: Option<usize>,
+    ) -> Vec<Suggestion> {
+        let input = self.case_mapping.lower_case(input);
+        let choices = self.choices.get(&input);
+        let similar_choices: Vec<&HashMap<SmolStr, u32>> = self
+            .choices
+            .iter()
+            .filter(|(x, _)| is_one_edit_apart(x, &input))
+            .map(|(_, x)| x)
+            .collect();
+
+        for sugg in suggestions.iter_mut() {
+            let value = self.case_mapping.lower_case(sugg.value());
+            let exact = choices.and_then(|x| x.get(&value)).copied().unwrap_or(0);
+            let similar = similar_choices
+                .iter()
+                .filter_map(|x| x.get(&value))
+                .fold(0u32, |sum, x| sum.saturating_add(*x));
+
+            let bonus =
+                self.bonus * exact as Weight + self.bonus * self.similar_factor * similar as Weight;
+            sugg.weight = (sugg.weight - bonus.min(self.max_bonus)).max(0.0);
+        }
+
+        suggestions.sort();
+
+        if let Some(n_best) = n_best {
+            suggestions.truncate(n_best);
+        }
+
+        suggestions
+    }
+
+    /// Forgets everything learned.
+    pub fn reset(&mut self) {
+        self.choices.clear();
+    }
+}
+
+/// Whether `b` is `a` with one letter inserted, deleted or substituted, or two neighbouring
+/// letters swapped.
+fn is_one_edit_apart(a: &str, b: &str) -> bool {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let (short, long) = if a.len() <= b.len() {
+        (&a, &b)
+    } else {
+        (&b, &a)
+    };
+
+    if long.len() - short.len() > 1 {
+        return false;
+    }
+
+    let prefix = short
+        .iter()
+        .zip(long.iter())
+        .take_while(|(x, y)| x == y)
+        .count();
+
+    if prefix == long.len() {
+        return false;
+    }
+
+    if short.len() < long.len() {
+        return short[prefix..] == long[prefix + 1..];
+    }
+
+    short[prefix + 1..] == long[prefix + 1..]
+        || (prefix + 1 < short.len()
+            && short[prefix] == long[prefix + 1]
+            && short[prefix + 1] == long[prefix]
+            && short[prefix + 2..] == long[prefix + 2..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chosen_suggestion_moves_up() {
+        let suggestions = vec![
+            Suggestion::new("ten".into(), 1.0),
+            Suggestion::new("tea".into(), 2.0),
+            Suggestion::new("the".into(), 3.0),
+        ];
+
+        let mut reranker = Reranker::new();
+        reranker.record("teh", "the");
+        reranker.record("Teh", "the");
+
+        let out = reranker.apply("teh", suggestions.clone(), Some(2));
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[0].value(), "the");
+
+        // Similar inputs only get a share of the bonus, and others none.
+        let out = reranker.apply("tee", suggestions.clone(), None);
+        assert_eq!(out[0].value(), "ten");
+        assert_eq!(out[2].weight(), 2.0);
+
+        let out = reranker.apply("hte", suggestions.clone(), None);
+        assert_eq!(out[2].value(), "the");
+        assert_eq!(out[2].weight(), 3.0);
+
+        reranker.reset();
+        let out = reranker.apply("teh", suggestions, None);
+        assert_eq!(out[0].value(), "ten");
+    }
+
+    #[test]
+    fn one_edit_apart() {
+        assert!(is_one_edit_apart("teh", "tee"));
+        assert!(is_one_edit_apart("teh", "te"));
+        assert!(is_one_edit_apart("teh", "tehh"));
+        assert!(is_one_edit_apart("teh", "the"));
+        assert!(!is_one_edit_apart("teh", "teh"));
+        assert!(!is_one_edit_apart("teh", "hte"));
+        assert!(!is_one_edit_apart("teh", "t"));
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let mut reranker = Reranker::new();
+        reranker.case_mapping = CaseMapping::Turkic;
+        reranker.record("teh", "the");
+
+        let json = serde_json::to_string(&reranker).unwrap();
+        let restored: Reranker = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.case_mapping, CaseMapping::Turkic);
+
+        let suggestions = vec![
+            Suggestion::new("ten".into(), 1.0),
+            Suggestion::new("the".into(), 2.0),
+        ];
+        assert_eq!(
+            restored.apply("teh", suggestions.clone(), None),
+            reranker.apply("teh", suggestions, None)
+        );
+
+        // States saved before the case mapping was kept load with the default one.
+        let restored: Reranker = serde_json::from_str(
+            r#"{"bonus":1.0,"similar_factor":0.5,"max_bonus":5.0,"choices":{}}"#,
+        )
+        .unwrap();
+        assert_eq!(restored.case_mapping, CaseMapping::Default);
+    }
+}
@@ -1,18 +1,82 @@
+use crate::speller::analysis::Analysis;
 use crate::types::Weight;
 use serde::{Deserialize, Serialize};
 use smol_str::SmolStr;
 use std::cmp::Ordering;
 use std::cmp::Ordering::Equal;
+use std::ops::Range;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EditKind {
+    Substitution,
+    Insertion,
+    Deletion,
+    Transposition,
+}
+
+/// A single error model edit on the path that produced a suggestion.
+///
+/// Indices count symbols, which are the characters of the input unless the
+/// alphabet has multicharacter symbols.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Edit {
+    pub kind: EditKind,
+    pub input_index: usize,
+    pub output_index: usize,
+    pub input: SmolStr,
+    pub output: SmolStr,
+    pub weight: Weight,
+}
+
+impl Edit {
+    fn lengths(&self) -> (usize, usize) {
+        match self.kind {
+            EditKind::Substitution => (1, 1),
+            EditKind::Insertion => (0, 1),
+            EditKind::Deletion => (1, 0),
+            EditKind::Transposition => (2, 2),
+        }
+    }
+
+    /// The range of input symbols this edit replaced.
+    pub fn input_range(&self) -> Range<usize> {
+        self.input_index..self.input_index + self.lengths().0
+    }
+
+    /// The range of suggestion symbols this edit produced, for highlighting.
+    pub fn output_range(&self) -> Range<usize> {
+        self.output_index..self.output_index + self.lengths().1
+    }
+}
+
+/// The neighbouring word a suggestion absorbs, when it joins two words into one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Merge {
+    Previous,
+    Next,
+}
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Suggestion {
     pub value: SmolStr,
     pub weight: Weight,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub analyses: Option<Vec<Analysis>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edits: Option<Vec<Edit>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merge: Option<Merge>,
 }
 
 impl Suggestion {
     pub fn new(value: SmolStr, weight: Weight) -> Suggestion {
-        Suggestion { value, weight }
+        Suggestion {
+            value,
+            weight,
+            analyses: None,
+            edits: None,
+            merge: None,
+        }
     }
 
     pub fn value(&self) -> &str {
@@ -22,6 +86,22 @@ impl Suggestion {
     pub fn weight(&self) -> Weight {
         self.weight
     }
+
+    /// Analyses of this suggestion, if `SpellerConfig::with_analyses` was set.
+    pub fn analyses(&self) -> Option<&[Analysis]> {
+        self.analyses.as_deref()
+    }
+
+    /// Error model edits of the best path to this suggestion, if `SpellerConfig::with_edits`
+    /// was set.
+    pub fn edits(&self) -> Option<&[Edit]> {
+        self.edits.as_deref()
+    }
+
+    /// The neighbouring word this suggestion replaces along with the current one, if any.
+    pub fn merge(&self) -> Option<Merge> {
+        self.merge
+    }
 }
 
 impl PartialOrd for Suggestion {
@@ -49,3 +129,12 @@ impl PartialEq for Suggestion {
 }
 
 impl Eq for Suggestion {}
+
+/// Suggestions from a search that may have been cut short.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SuggestionResult {
+    pub suggestions: Vec<Suggestion>,
+    /// Whether the search stopped early, due to a deadline, cancellation or the iteration
+    /// limit, so that better suggestions may have been missed.
+    pub is_partial: bool,
+}
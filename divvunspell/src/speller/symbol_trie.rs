@@ -0,0 +1,120 @@
+use hashbrown::HashMap;
+use smol_str::SmolStr;
+
+use crate::types::SymbolNumber;
+
+#[derive(Debug, Default)]
+struct TrieNode {
+    children: HashMap<char, usize>,
+    symbol: Option<SymbolNumber>,
+}
+
+/// The symbols of an alphabet by their characters, for splitting words into symbols by
+/// longest match, so that multi-character symbols such as digraphs are kept whole.
+#[derive(Debug)]
+pub(crate) struct SymbolTrie {
+    nodes: Vec<TrieNode>,
+}
+
+impl SymbolTrie {
+    pub(crate) fn new(string_to_symbol: &HashMap<SmolStr, SymbolNumber>) -> SymbolTrie {
+        let mut trie = SymbolTrie {
+            nodes: vec![TrieNode::default()],
+        };
+
+        for (string, &symbol) in string_to_symbol.iter() {
+            if string.is_empty() {
+                continue;
+            }
+
+            let mut index = 0;
+
+            for ch in string.chars() {
+                index = match trie.nodes[index].children.get(&ch) {
+                    Some(&next) => next,
+                    None => {
+                        let next = trie.nodes.len();
+                        trie.nodes.push(TrieNode::default());
+                        trie.nodes[index].children.insert(ch, next);
+                        next
+                    }
+                };
+            }
+
+            trie.nodes[index].symbol = Some(symbol);
+        }
+
+        trie
+    }
+
+    /// The longest symbol `s` starts with, and its length in bytes.
+    pub(crate) fn longest_match(&self, s: &str) -> Option<(SymbolNumber, usize)> {
+        self.longest_match_by(s, |_| true)
+    }
+
+    /// Like `longest_match`, but only for symbols whose length in bytes passes `is_end`.
+    pub(crate) fn longest_match_by<P>(&self, s: &str, is_end: P) -> Option<(SymbolNumber, usize)>
+    where
+        P: Fn(usize) -> bool,
+    {
+        let mut index = 0;
+        let mut found = None;
+
+        for (offset, ch) in s.char_indices() {
+            index = match self.nodes[index].children.get(&ch) {
+                Some(&next) => next,
+                None => break,
+            };
+
+            let len = offset + ch.len_utf8();
+
+            if let Some(symbol) = self.nodes[index].symbol {
+                if is_end(len) {
+                    found = Some((symbol, len));
+                }
+            }
+        }
+
+        found
+    }
+
+    /// Splits all of `s` into symbols by longest match, with their lengths in bytes, or
+    /// gives `None` if some part of it is not a symbol.
+    pub(crate) fn split(&self, mut s: &str) -> Option<Vec<(SymbolNumber, usize)>> {
+        let mut symbols = vec![];
+
+        while !s.is_empty() {
+            let (symbol, len) = self.longest_match(s)?;
+            symbols.push((symbol, len));
+            s = &s[len..];
+        }
+
+        Some(symbols)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn longest_match() {
+        let mut symbols = HashMap::new();
+        symbols.insert(SmolStr::from("d"), 1);
+        symbols.insert(SmolStr::from("dz"), 2);
+        symbols.insert(SmolStr::from("dzs"), 3);
+        symbols.insert(SmolStr::from("a\u{301}"), 4);
+        let trie = SymbolTrie::new(&symbols);
+
+        assert_eq!(trie.longest_match("dzsa"), Some((3, 3)));
+        assert_eq!(trie.longest_match("dza"), Some((2, 2)));
+        assert_eq!(trie.longest_match("da"), Some((1, 1)));
+        assert_eq!(trie.longest_match("a\u{301}"), Some((4, 3)));
+        assert_eq!(trie.longest_match("a"), None);
+        assert_eq!(trie.longest_match(""), None);
+        assert_eq!(trie.longest_match_by("dzsa", |x| x < 3), Some((2, 2)));
+
+        assert_eq!(trie.split("dzd"), Some(vec![(2, 2), (1, 1)]));
+        assert_eq!(trie.split("da"), None);
+    }
+}
@@ -0,0 +1,24 @@
+//! Spellers over small in-memory transducers, for tests.
+
+use std::sync::Arc;
+
+use super::Speller;
+use crate::transducer::hfst::HfstTransducer;
+use crate::transducer::testing;
+use crate::types::Weight;
+
+pub(crate) type TestSpeller =
+    Speller<std::fs::File, HfstTransducer<std::fs::File>, HfstTransducer<std::fs::File>>;
+
+/// A speller for `words`, with an error model of one edit weighing 1 over their letters.
+pub(crate) fn speller(words: &[(&str, Weight)]) -> Arc<TestSpeller> {
+    let mut alphabet: Vec<char> = words.iter().flat_map(|x| x.0.chars()).collect();
+    alphabet.sort();
+    alphabet.dedup();
+    let alphabet: String = alphabet.into_iter().collect();
+
+    Speller::new(
+        testing::edit_distance(&alphabet, 1.0),
+        testing::lexicon(words, false),
+    )
+}
@@ -0,0 +1,535 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+use hashbrown::HashSet;
+use smol_str::SmolStr;
+
+use super::SpellerConfig;
+use crate::speller::suggestion::{Suggestion, SuggestionResult};
+use crate::speller::word_form::{FormedWords, WordForm};
+use crate::transducer::Transducer;
+use crate::types::{SymbolNumber, TransitionTableIndex, Weight};
+
+#[derive(Debug, Default)]
+struct TrieNode {
+    parent: usize,
+    ch: char,
+    children: BTreeMap<char, usize>,
+    /// How many words have this form.
+    count: usize,
+}
+
+/// Words added at runtime, on top of the lexicon.
+///
+/// The words are kept in a trie that the suggestion search walks through the error model
+/// alongside the lexicon, so misspellings of user words are corrected like any other. The
+/// trie holds them in the speller's `WordForm`, as they would be looked up, while the words
+/// themselves are kept as added. On disk, a user dictionary is a UTF-8 text file with one
+/// word per line.
+#[derive(Debug)]
+pub struct UserDictionary {
+    nodes: Vec<TrieNode>,
+    words: BTreeSet<SmolStr>,
+    weight: Weight,
+    form: WordForm,
+    form_generation: u64,
+}
+
+impl Default for UserDictionary {
+    fn default() -> UserDictionary {
+        UserDictionary::new()
+    }
+}
+
+impl UserDictionary {
+    /// The weight a user word has by default, about that of an uncommon word in a
+    /// frequency weighted lexicon, so that user words do not crowd out common ones.
+    pub const DEFAULT_WEIGHT: Weight = 10.0;
+
+    pub fn new() -> UserDictionary {
+        UserDictionary {
+            nodes: vec![TrieNode::default()],
+            words: BTreeSet::new(),
+            weight: UserDictionary::DEFAULT_WEIGHT,
+            form: WordForm::default(),
+            form_generation: 0,
+        }
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> std::io::Result<UserDictionary> {
+        let file = std::fs::File::open(path)?;
+        let mut dict = UserDictionary::new();
+
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            let word = line.trim();
+
+            if !word.is_empty() {
+                dict.insert(word);
+            }
+        }
+
+        Ok(dict)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+
+        for word in self.words() {
+            writeln!(file, "{}", word)?;
+        }
+
+        file.flush()
+    }
+
+    /// Adds a word, returning whether it was new.
+    pub fn insert(&mut self, word: &str) -> bool {
+        if !self.words.insert(word.into()) {
+            return false;
+        }
+
+        let word = self.form.normalize(word);
+        self.insert_formed(&word);
+        true
+    }
+
+    fn insert_formed(&mut self, word: &str) {
+        let mut index = 0;
+
+        for ch in word.chars() {
+            index = match self.nodes[index].children.get(&ch) {
+                Some(&next) => next,
+                None => {
+                    let next = self.nodes.len();
+                    self.nodes.push(TrieNode {
+                        parent: index,
+                        ch,
+                        ..TrieNode::default()
+                    });
+                    self.nodes[index].children.insert(ch, next);
+                    next
+                }
+            };
+        }
+
+        self.nodes[index].count += 1;
+    }
+
+    /// Removes a word, returning whether it was present.
+    pub fn remove(&mut self, word: &str) -> bool {
+        if !self.words.remove(word) {
+            return false;
+        }
+
+        let word = self.form.normalize(word);
+
+        if let Some(index) = self.find(0, &word) {
+            self.nodes[index].count -= 1;
+        }
+
+        true
+    }
+
+    /// Whether `word`, or another word of the same form, has been added.
+    pub fn contains(&self, word: &str) -> bool {
+        self.contains_formed(&self.form.normalize(word))
+    }
+
+    /// Like `contains`, for a word already in the dictionary's form.
+    pub(crate) fn contains_formed(&self, word: &str) -> bool {
+        self.find(0, word)
+            .map(|index| self.nodes[index].count > 0)
+            .unwrap_or(false)
+    }
+
+    /// All words as they were added, in sorted order.
+    pub fn words(&self) -> Vec<SmolStr> {
+        self.words.iter().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// The weight of a user word, which its suggestions carry on top of the error model's
+    /// weight as lexicon words carry theirs.
+    pub fn weight(&self) -> Weight {
+        self.weight
+    }
+
+    pub fn set_weight(&mut self, weight: Weight) {
+        self.weight = weight;
+    }
+
+    #[inline(always)]
+    fn find(&self, mut index: usize, s: &str) -> Option<usize> {
+        for ch in s.chars() {
+            index = *self.nodes[index].children.get(&ch)?;
+        }
+
+        Some(index)
+    }
+
+    /// Runs the error model over `input` against the words of this dictionary, returning
+    /// up to `config.n_best` suggestions, lightest first. `typed` holds the text of each
+    /// input symbol, which identity and unknown arcs copy through. Suggestions are in the
+    /// dictionary's form, as the lexicon's are in its own.
+    pub(crate) fn suggest<F, T>(
+        &self,
+        mutator: &T,
+        input: &[SymbolNumber],
+        typed: &[SmolStr],
+        config: &SpellerConfig,
+    ) -> SuggestionResult
+    where
+        F: crate::vfs::File,
+        T: Transducer<F>,
+    {
+        if self.is_empty() || input.is_empty() {
+            return SuggestionResult::default();
+        }
+
+        let mut search = UserSearch {
+            dict: self,
+            mutator,
+            input,
+            typed,
+            max_weight: config.max_weight.unwrap_or(f32::MAX),
+            queue: BinaryHeap::new(),
+            _file: std::marker::PhantomData,
+        };
+
+        search.run(config)
+    }
+}
+
+impl FormedWords for UserDictionary {
+    fn form_generation(&self) -> u64 {
+        self.form_generation
+    }
+
+    fn set_form(&mut self, form: &WordForm, generation: u64) {
+        self.form = form.clone();
+        self.form_generation = generation;
+        self.nodes = vec![TrieNode::default()];
+
+        let words: Vec<SmolStr> = self.words.iter().map(|x| form.normalize(x)).collect();
+        for word in words.iter() {
+            self.insert_formed(word);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SearchNode {
+    weight: Weight,
+    input_state: usize,
+    mutator_state: TransitionTableIndex,
+    trie_node: usize,
+    is_final: bool,
+}
+
+impl Eq for SearchNode {}
+
+impl PartialOrd for SearchNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SearchNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed, so that the max-heap pops the lightest node first.
+        other
+            .weight
+            .partial_cmp(&self.weight)
+            .unwrap_or(Ordering::Equal)
+            .then(self.is_final.cmp(&other.is_final))
+    }
+}
+
+struct UserSearch<'a, F: crate::vfs::File, T: Transducer<F>> {
+    dict: &'a UserDictionary,
+    mutator: &'a T,
+    input: &'a [SymbolNumber],
+    typed: &'a [SmolStr],
+    max_weight: Weight,
+    queue: BinaryHeap<SearchNode>,
+    _file: std::marker::PhantomData<F>,
+}
+
+impl<F, T> UserSearch<'_, F, T>
+where
+    F: crate::vfs::File,
+    T: Transducer<F>,
+{
+    fn run(&mut self, config: &SpellerConfig) -> SuggestionResult {
+        let mut visited = HashSet::new();
+        let mut seen = HashSet::new();
+        let mut suggestions = vec![];
+        let mut iteration_count = 0usize;
+        let mut is_partial = false;
+
+        // Every word carries the same weight, so it may as well be paid up front.
+        self.queue.push(SearchNode {
+            weight: self.dict.weight,
+            input_state: 0,
+            mutator_state: 0,
+            trie_node: 0,
+            is_final: false,
+        });
+
+        while let Some(node) = self.queue.pop() {
+            if iteration_count % 1024 == 0 && config.is_interrupted() {
+                is_partial = true;
+                break;
+            }
+
+            iteration_count += 1;
+
+            if node.is_final {
+                let word = self.word_at(node.trie_node);
+
+                if seen.insert(word.clone()) {
+                    suggestions.push(Suggestion::new(word, node.weight));
+                }
+
+                if config
+                    .n_best
+                    .map(|n| suggestions.len() >= n)
+                    .unwrap_or(false)
+                {
+                    break;
+                }
+
+                continue;
+            }
+
+            if !visited.insert((node.input_state, node.mutator_state, node.trie_node)) {
+                continue;
+            }
+
+            self.mutator_epsilons(&node);
+
+            if node.input_state < self.input.len() {
+                self.consume_input(&node);
+            } else if self.mutator.is_final(node.mutator_state)
+                && self.dict.nodes[node.trie_node].count > 0
+            {
+                let weight = node.weight + self.mutator.final_weight(node.mutator_state).unwrap();
+                self.push(SearchNode {
+                    weight,
+                    is_final: true,
+                    ..node
+                });
+            }
+        }
+
+        SuggestionResult {
+            suggestions,
+            is_partial,
+        }
+    }
+
+    #[inline(always)]
+    fn push(&mut self, node: SearchNode) {
+        if node.weight <= self.max_weight {
+            self.queue.push(node);
+        }
+    }
+
+    fn mutator_epsilons(&mut self, node: &SearchNode) {
+        let mutator = self.mutator;
+
+        if !mutator.has_transitions(node.mutator_state + 1, Some(0)) {
+            return;
+        }
+
+        let key_table = mutator.alphabet().key_table();
+        let identity = mutator.alphabet().identity();
+        let unknown = mutator.alphabet().unknown();
+        let mut next_m = mutator.next(node.mutator_state, 0).unwrap();
+
+        while let Some(transition) = mutator.take_epsilons(next_m) {
+            next_m += 1;
+
+            let sym = transition.symbol().unwrap_or(0);
+
+            // Identity and unknown have nothing to copy when no input is consumed.
+            if sym != 0 && (Some(sym) == identity || Some(sym) == unknown) {
+                continue;
+            }
+
+            let trie_node = match self.dict.find(node.trie_node, &key_table[sym as usize]) {
+                Some(v) => v,
+                None => continue,
+            };
+
+            self.push(SearchNode {
+                weight: node.weight + transition.weight().unwrap(),
+                mutator_state: transition.target().unwrap(),
+                trie_node,
+                ..*node
+            });
+        }
+    }
+
+    fn consume_input(&mut self, node: &SearchNode) {
+        let mutator = self.mutator;
+        let input_sym = self.input[node.input_state];
+
+        if mutator.has_transitions(node.mutator_state + 1, Some(input_sym)) {
+            self.mutator_arcs(node, input_sym);
+        } else if input_sym >= mutator.alphabet().initial_symbol_count() {
+            for sym in [mutator.alphabet().identity(), mutator.alphabet().unknown()].iter() {
+                if let Some(sym) = *sym {
+                    if mutator.has_transitions(node.mutator_state + 1, Some(sym)) {
+                        self.mutator_arcs(node, sym);
+                    }
+                }
+            }
+        }
+    }
+
+    fn mutator_arcs(&mut self, node: &SearchNode, input_sym: SymbolNumber) {
+        let mutator = self.mutator;
+        let key_table = mutator.alphabet().key_table();
+        let identity = mutator.alphabet().identity();
+        let unknown = mutator.alphabet().unknown();
+        let mut next_m = mutator.next(node.mutator_state, input_sym).unwrap();
+
+        while let Some(transition) = mutator.take_non_epsilons(next_m, input_sym) {
+            next_m += 1;
+
+            let sym = transition.symbol().unwrap_or(0);
+            let output = if sym != 0 && (Some(sym) == identity || Some(sym) == unknown) {
+                &*self.typed[node.input_state]
+            } else {
+                &*key_table[sym as usize]
+            };
+
+            let trie_node = match self.dict.find(node.trie_node, output) {
+                Some(v) => v,
+                None => continue,
+            };
+
+            self.push(SearchNode {
+                weight: node.weight + transition.weight().unwrap(),
+                input_state: node.input_state + 1,
+                mutator_state: transition.target().unwrap(),
+                trie_node,
+                is_final: false,
+            });
+        }
+    }
+
+    fn word_at(&self, mut index: usize) -> SmolStr {
+        let mut chars = vec![];
+
+        while index != 0 {
+            let node = &self.dict.nodes[index];
+            chars.push(node.ch);
+            index = node.parent;
+        }
+
+        chars.into_iter().rev().collect::<String>().into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::speller::normalizer::NormalizerRule;
+    use crate::speller::testing::speller;
+
+    #[test]
+    fn suggest() {
+        let speller = speller(&[("abc", 5.0)]);
+        speller.user_dictionary_mut().insert("abb");
+        let config = SpellerConfig {
+            case_handling: None,
+            ..SpellerConfig::default()
+        };
+        let values = |word: &str| {
+            speller
+                .clone()
+                .suggest_with_config(word, &config)
+                .into_iter()
+                .map(|x| (x.value, x.weight))
+                .collect::<Vec<_>>()
+        };
+
+        // A user word weighs as much as a lexicon word might, on top of the edit.
+        assert_eq!(
+            values("aba"),
+            vec![("abc".into(), 6.0), ("abb".into(), 11.0)]
+        );
+
+        speller.user_dictionary_mut().set_weight(0.0);
+        assert_eq!(
+            values("aba"),
+            vec![("abb".into(), 1.0), ("abc".into(), 6.0)]
+        );
+
+        // Words are looked up as the speller normalizes them, whenever they were added.
+        speller.user_dictionary_mut().insert("bäb");
+        assert!(!speller.clone().is_correct("bab"));
+
+        speller.normalizer_mut().push(NormalizerRule::Replace {
+            from: "ä".into(),
+            to: "a".into(),
+        });
+        assert!(speller.clone().is_correct("bab"));
+        assert!(speller.clone().is_correct("bäb"));
+        assert!(speller.user_dictionary().contains("bäb"));
+        assert_eq!(
+            values("bac"),
+            vec![("bab".into(), 1.0), ("abc".into(), 6.0)]
+        );
+
+        speller.user_dictionary_mut().insert("cab");
+        assert!(speller.clone().is_correct("cab"));
+        assert_eq!(speller.user_dictionary().words(), vec!["abb", "bäb", "cab"]);
+    }
+
+    #[test]
+    fn insert_remove_and_list() {
+        let mut dict = UserDictionary::new();
+        assert!(dict.insert("Ánde"));
+        assert!(dict.insert("And"));
+        assert!(!dict.insert("And"));
+        assert!(dict.contains("Ánde"));
+        assert!(!dict.contains("Án"));
+        assert_eq!(dict.words(), vec!["And", "Ánde"]);
+
+        assert!(dict.remove("Ánde"));
+        assert!(!dict.remove("Ánde"));
+        assert!(!dict.contains("Ánde"));
+        assert_eq!(dict.len(), 1);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let path =
+            std::env::temp_dir().join(format!("divvunspell-user-{}.txt", std::process::id()));
+
+        let mut dict = UserDictionary::new();
+        dict.insert("Ánde");
+        dict.insert("čáhppes");
+        dict.insert("And");
+        dict.save(&path).unwrap();
+
+        let loaded = UserDictionary::from_path(&path);
+        std::fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.words(), dict.words());
+        assert!(loaded.contains("čáhppes"));
+    }
+}
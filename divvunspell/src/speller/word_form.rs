@@ -0,0 +1,49 @@
+use smol_str::SmolStr;
+
+use crate::speller::normalization::NormalizationForm;
+use crate::speller::normalizer::Normalizer;
+use crate::tokenizer::case_handling::CaseMapping;
+
+/// How a speller writes words before looking them up: in the lexicon's normalization form,
+/// run through its normalizer, with case mapped as for its locale. Word lists kept beside
+/// the lexicon hold their entries in the same form, so that they match what is looked up.
+#[derive(Debug, Clone)]
+pub struct WordForm {
+    pub case_mapping: CaseMapping,
+    pub normalization: Option<NormalizationForm>,
+    pub normalizer: Normalizer,
+}
+
+impl Default for WordForm {
+    fn default() -> WordForm {
+        WordForm {
+            case_mapping: CaseMapping::Default,
+            normalization: None,
+            normalizer: Normalizer::default(),
+        }
+    }
+}
+
+impl WordForm {
+    /// `word` as the speller looks it up.
+    pub fn normalize(&self, word: &str) -> SmolStr {
+        let word = match self.normalization {
+            Some(form) => form.normalize(word),
+            None => word.into(),
+        };
+
+        self.normalizer.normalize(&word).word
+    }
+
+    pub fn lower_case(&self, word: &str) -> SmolStr {
+        self.case_mapping.lower_case(word)
+    }
+}
+
+/// A word list that keeps its entries in a speller's `WordForm`. The speller hands it a
+/// new form, with the generation of its settings, whenever they change.
+pub(crate) trait FormedWords {
+    fn form_generation(&self) -> u64;
+
+    fn set_form(&mut self, form: &WordForm, generation: u64);
+}
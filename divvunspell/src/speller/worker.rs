@@ -1,13 +1,16 @@
 use hashbrown::HashMap;
 use smol_str::SmolStr;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
 use std::f32;
 use std::sync::Arc;
 
 use lifeguard::{Pool, Recycled};
 
 use super::{Speller, SpellerConfig};
-use crate::speller::suggestion::Suggestion;
-use crate::transducer::tree_node::TreeNode;
+use crate::speller::analysis::Analysis;
+use crate::speller::suggestion::{Edit, EditKind, Suggestion, SuggestionResult};
+use crate::transducer::tree_node::{MutatorEdit, TreeNode, VisitedNodes};
 use crate::transducer::Transducer;
 use crate::types::{SymbolNumber, Weight};
 
@@ -19,14 +22,91 @@ fn speller_start_node(pool: &Pool<TreeNode>, size: usize) -> Vec<Recycled<TreeNo
     nodes
 }
 
+#[inline(always)]
+fn insert_analysis(analyses: &mut HashMap<SmolStr, Analysis>, analysis: Analysis) {
+    match analyses.get_mut(&analysis.value) {
+        Some(entry) if entry.weight > analysis.weight => *entry = analysis,
+        Some(_) => {}
+        None => {
+            analyses.insert(analysis.value.clone(), analysis);
+        }
+    }
+}
+
+/// An entry in the best-first queue. `BinaryHeap` is a max-heap, so the ordering of
+/// `TreeNode` is reversed to pop the lightest node first.
+struct QueueNode {
+    node: TreeNode,
+    is_final: bool,
+}
+
+impl QueueNode {
+    #[inline(always)]
+    fn new(node: TreeNode, is_final: bool) -> QueueNode {
+        QueueNode { node, is_final }
+    }
+}
+
+impl PartialEq for QueueNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for QueueNode {}
+
+impl PartialOrd for QueueNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueueNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .node
+            .cmp(&self.node)
+            .then(self.is_final.cmp(&other.is_final))
+    }
+}
+
+/// How many nodes a search expands before giving up on the word.
+const MAX_ITERATIONS: usize = 10_000_000;
+
 #[inline(always)]
 fn speller_max_weight(config: &SpellerConfig) -> Weight {
     config.max_weight.unwrap_or(f32::MAX)
 }
 
+/// A word as mutator symbols. Characters outside the mutator's alphabet get virtual symbols
+/// numbered past its end, which only mean anything within one search.
+pub(crate) struct InputSymbols {
+    pub(crate) symbols: Vec<SymbolNumber>,
+    /// The text of each symbol, as typed.
+    pub(crate) strings: Vec<SmolStr>,
+    pub(crate) virtual_symbols: Vec<SmolStr>,
+}
+
+#[inline(always)]
+fn string_from_symbols(key_table: &[SmolStr], syms: &[SymbolNumber]) -> SmolStr {
+    syms.iter().map(|s| &*key_table[*s as usize]).collect()
+}
+
+/// `key_table` followed by `extra`, if there is any.
+fn extend_key_table(key_table: &[SmolStr], extra: &[SmolStr]) -> Option<Vec<SmolStr>> {
+    if extra.is_empty() {
+        return None;
+    }
+
+    Some(key_table.iter().chain(extra.iter()).cloned().collect())
+}
+
 pub struct SpellerWorker<F: crate::vfs::File, T: Transducer<F>, U: Transducer<F>> {
     speller: Arc<Speller<F, T, U>>,
     input: Vec<SymbolNumber>,
+    /// The key tables extended with the input's virtual symbols, if it has any.
+    mutator_keys: Option<Vec<SmolStr>>,
+    lexicon_keys: Option<Vec<SmolStr>>,
     config: SpellerConfig,
 }
 
@@ -40,16 +120,55 @@ where
     #[inline(always)]
     pub(crate) fn new(
         speller: Arc<Speller<F, T, U>>,
-        input: Vec<SymbolNumber>,
+        input: InputSymbols,
         config: SpellerConfig,
     ) -> SpellerWorker<F, T, U> {
+        let mutator_keys = extend_key_table(
+            speller.mutator().alphabet().key_table(),
+            &input.virtual_symbols,
+        );
+        let lexicon_keys = extend_key_table(
+            speller.lexicon().alphabet().key_table(),
+            &input.virtual_symbols,
+        );
+
         SpellerWorker {
             speller,
-            input,
+            input: input.symbols,
+            mutator_keys,
+            lexicon_keys,
             config,
         }
     }
 
+    fn mutator_keys(&self) -> &[SmolStr] {
+        match &self.mutator_keys {
+            Some(keys) => keys,
+            None => self.speller.mutator().alphabet().key_table(),
+        }
+    }
+
+    fn lexicon_keys(&self) -> &[SmolStr] {
+        match &self.lexicon_keys {
+            Some(keys) => keys,
+            None => self.speller.lexicon().alphabet().key_table(),
+        }
+    }
+
+    /// The lexicon symbol for mutator symbol `sym`, including virtual ones.
+    #[inline(always)]
+    fn translate(&self, sym: SymbolNumber) -> SymbolNumber {
+        let alphabet_translator = self.speller.alphabet_translator();
+
+        match alphabet_translator.get(sym as usize) {
+            Some(&x) => x,
+            None => {
+                let offset = sym as usize - alphabet_translator.len();
+                (self.speller.lexicon().alphabet().key_table().len() + offset) as SymbolNumber
+            }
+        }
+    }
+
     #[inline(always)]
     fn lexicon_epsilons<'a>(
         &self,
@@ -133,6 +252,7 @@ where
 
             if let Some(sym) = transition.symbol() {
                 let trans_sym = alphabet_translator[sym as usize];
+                let edit = self.mutator_edit(next_node, 0, sym, transition.weight().unwrap());
 
                 if !lexicon.has_transitions(next_node.lexicon_state + 1, Some(trans_sym)) {
                     // we have no regular transitions for this
@@ -148,9 +268,11 @@ where
                                 max_weight,
                                 &next_node,
                                 lexicon.alphabet().unknown().unwrap(),
+                                trans_sym,
                                 transition.target().unwrap(),
                                 transition.weight().unwrap(),
                                 0,
+                                edit,
                                 output_nodes,
                             );
                         }
@@ -164,9 +286,11 @@ where
                                 max_weight,
                                 &next_node,
                                 lexicon.alphabet().identity().unwrap(),
+                                trans_sym,
                                 transition.target().unwrap(),
                                 transition.weight().unwrap(),
                                 0,
+                                edit,
                                 output_nodes,
                             );
                         }
@@ -181,9 +305,11 @@ where
                     max_weight,
                     &next_node,
                     trans_sym,
+                    trans_sym,
                     transition.target().unwrap(),
                     transition.weight().unwrap(),
                     0,
+                    edit,
                     output_nodes,
                 );
             }
@@ -192,6 +318,9 @@ where
         }
     }
 
+    /// Queues the lexicon arcs on `input_sym` out of `next_node`. `surface_sym` is the symbol
+    /// the mutator wrote, which identity and unknown arcs stand in for; it is not always the
+    /// typed one, as the mutator may have inserted it.
     #[inline(always)]
     fn queue_lexicon_arcs<'a>(
         &self,
@@ -199,9 +328,11 @@ where
         max_weight: Weight,
         next_node: &TreeNode,
         input_sym: SymbolNumber,
+        surface_sym: SymbolNumber,
         mutator_state: u32,
         mutator_weight: Weight,
         input_increment: i16,
+        edit: Option<MutatorEdit>,
         output_nodes: &mut Vec<Recycled<'a, TreeNode>>,
     ) {
         let lexicon = self.speller.lexicon();
@@ -213,10 +344,8 @@ where
         while let Some(noneps_trans) = lexicon.take_non_epsilons(next, input_sym) {
             if let Some(mut sym) = noneps_trans.symbol() {
                 // Symbol replacement here is unfortunate but necessary.
-                if let Some(id) = identity {
-                    if sym == id {
-                        sym = self.input[next_node.input_state as usize];
-                    }
+                if identity == Some(sym) {
+                    sym = surface_sym;
                 }
 
                 let is_under_weight_limit = self.is_under_weight_limit(
@@ -225,7 +354,7 @@ where
                 );
 
                 if is_under_weight_limit {
-                    let new_node = next_node.update(
+                    let mut new_node = next_node.update(
                         pool,
                         sym,
                         Some(next_node.input_state + input_increment as u32),
@@ -234,6 +363,14 @@ where
                         noneps_trans.weight().unwrap() + mutator_weight,
                     );
 
+                    if self.config.with_analyses {
+                        new_node.surface.push(surface_sym);
+                    }
+
+                    if let Some(edit) = edit {
+                        new_node.edits.push(edit);
+                    }
+
                     output_nodes.push(new_node);
                 }
             }
@@ -253,9 +390,10 @@ where
     ) {
         let mutator = self.speller.mutator();
         let lexicon = self.speller.lexicon();
-        let alphabet_translator = self.speller.alphabet_translator();
+        let identity = mutator.alphabet().identity();
 
         let mut next_m = mutator.next(next_node.mutator_state, input_sym).unwrap();
+        let typed = self.input[next_node.input_state as usize];
 
         while let Some(transition) = mutator.take_non_epsilons(next_m, input_sym) {
             let symbol = transition.symbol();
@@ -263,7 +401,7 @@ where
             if let Some(0) = symbol {
                 let transition_weight = transition.weight().unwrap();
                 if self.is_under_weight_limit(max_weight, next_node.weight() + transition_weight) {
-                    let new_node = next_node.update(
+                    let mut new_node = next_node.update(
                         pool,
                         0,
                         Some(next_node.input_state + 1),
@@ -272,6 +410,10 @@ where
                         transition_weight,
                     );
 
+                    if let Some(edit) = self.mutator_edit(next_node, typed, 0, transition_weight) {
+                        new_node.edits.push(edit);
+                    }
+
                     output_nodes.push(new_node);
                 }
 
@@ -279,8 +421,14 @@ where
                 continue;
             }
 
-            if let Some(sym) = symbol {
-                let trans_sym = alphabet_translator[sym as usize];
+            if let Some(mut sym) = symbol {
+                // An identity arc writes out what was typed, which may be a virtual symbol.
+                if Some(sym) == identity {
+                    sym = typed;
+                }
+
+                let trans_sym = self.translate(sym);
+                let edit = self.mutator_edit(next_node, typed, sym, transition.weight().unwrap());
 
                 if !lexicon.has_transitions(next_node.lexicon_state + 1, Some(trans_sym)) {
                     if trans_sym >= lexicon.alphabet().initial_symbol_count() {
@@ -293,9 +441,11 @@ where
                                 max_weight,
                                 &next_node,
                                 lexicon.alphabet().unknown().unwrap(),
+                                trans_sym,
                                 transition.target().unwrap(),
                                 transition.weight().unwrap(),
                                 1,
+                                edit,
                                 output_nodes,
                             );
                         }
@@ -308,9 +458,11 @@ where
                                 max_weight,
                                 &next_node,
                                 lexicon.alphabet().identity().unwrap(),
+                                trans_sym,
                                 transition.target().unwrap(),
                                 transition.weight().unwrap(),
                                 1,
+                                edit,
                                 output_nodes,
                             );
                         }
@@ -324,9 +476,11 @@ where
                     max_weight,
                     &next_node,
                     trans_sym,
+                    trans_sym,
                     transition.target().unwrap(),
                     transition.weight().unwrap(),
                     1,
+                    edit,
                     output_nodes,
                 );
 
@@ -352,6 +506,10 @@ where
 
         let input_sym = self.input[input_state];
 
+        if let Some(weight) = self.config.diacritic_weight {
+            self.queue_diacritic_arcs(pool, max_weight, next_node, weight, output_nodes);
+        }
+
         if !mutator.has_transitions(next_node.mutator_state + 1, Some(input_sym)) {
             // we have no regular transitions for this
             if input_sym >= mutator.alphabet().initial_symbol_count() {
@@ -393,45 +551,51 @@ where
         next_node: &TreeNode,
         output_nodes: &mut Vec<Recycled<'a, TreeNode>>,
     ) {
-        let mutator = self.speller.mutator();
         let lexicon = self.speller.lexicon();
-        let alphabet_translator = self.speller.alphabet_translator();
         let input_state = next_node.input_state as usize;
 
         if input_state >= self.input.len() {
             return;
         }
 
-        let input_sym = alphabet_translator[self.input[input_state as usize] as usize];
+        if self.config.accept_without_diacritics {
+            self.queue_diacritic_arcs(pool, max_weight, next_node, 0.0, output_nodes);
+        }
+
+        let input_sym = self.translate(self.input[input_state as usize]);
         let next_lexicon_state = next_node.lexicon_state + 1;
 
         if !lexicon.has_transitions(next_lexicon_state, Some(input_sym)) {
             // we have no regular transitions for this
             if input_sym >= lexicon.alphabet().initial_symbol_count() {
-                let identity = mutator.alphabet().identity();
+                let identity = lexicon.alphabet().identity();
                 if lexicon.has_transitions(next_lexicon_state, identity) {
                     self.queue_lexicon_arcs(
                         pool,
                         max_weight,
                         &next_node,
                         identity.unwrap(),
+                        input_sym,
                         next_node.mutator_state,
                         0.0,
                         1,
+                        None,
                         output_nodes,
                     );
                 }
 
-                let unknown = mutator.alphabet().unknown();
+                let unknown = lexicon.alphabet().unknown();
                 if lexicon.has_transitions(next_lexicon_state, unknown) {
                     self.queue_lexicon_arcs(
                         pool,
                         max_weight,
                         &next_node,
                         unknown.unwrap(),
+                        input_sym,
                         next_node.mutator_state,
                         0.0,
                         1,
+                        None,
                         output_nodes,
                     );
                 }
@@ -445,13 +609,90 @@ where
             max_weight,
             &next_node,
             input_sym,
+            input_sym,
             next_node.mutator_state,
             0.0,
             1,
+            None,
             output_nodes,
         );
     }
 
+    /// Consumes the next input symbol as the same letter with other diacritics, leaving the
+    /// mutator where it is.
+    #[inline(always)]
+    fn queue_diacritic_arcs<'a>(
+        &self,
+        pool: &'a Pool<TreeNode>,
+        max_weight: Weight,
+        next_node: &TreeNode,
+        weight: Weight,
+        output_nodes: &mut Vec<Recycled<'a, TreeNode>>,
+    ) {
+        let lexicon = self.speller.lexicon();
+        let typed = self.input[next_node.input_state as usize];
+
+        for &sym in self.speller.diacritic_variants(typed).iter() {
+            if !lexicon.has_transitions(next_node.lexicon_state + 1, Some(sym)) {
+                continue;
+            }
+
+            // Edits are in mutator symbols, which may lack the variant.
+            let edit = if self.config.with_edits {
+                let output = &lexicon.alphabet().key_table()[sym as usize];
+                self.speller
+                    .mutator()
+                    .alphabet()
+                    .key_table()
+                    .iter()
+                    .position(|x| x == output)
+                    .and_then(|x| self.mutator_edit(next_node, typed, x as SymbolNumber, weight))
+            } else {
+                None
+            };
+
+            self.queue_lexicon_arcs(
+                pool,
+                max_weight,
+                next_node,
+                sym,
+                sym,
+                next_node.mutator_state,
+                weight,
+                1,
+                edit,
+                output_nodes,
+            );
+        }
+    }
+
+    /// Records a mutator arc as an edit if edits were requested and the arc changes the input.
+    #[inline(always)]
+    fn mutator_edit(
+        &self,
+        next_node: &TreeNode,
+        input: SymbolNumber,
+        output: SymbolNumber,
+        weight: Weight,
+    ) -> Option<MutatorEdit> {
+        let alphabet = self.speller.mutator().alphabet();
+
+        if !self.config.with_edits
+            || input == output
+            || alphabet.identity() == Some(output)
+            || (input != 0 && alphabet.unknown() == Some(output))
+        {
+            return None;
+        }
+
+        Some(MutatorEdit {
+            input_state: next_node.input_state,
+            input,
+            output,
+            weight,
+        })
+    }
+
     #[inline(always)]
     fn update_weight_limit(&self, best_weight: Weight, suggestions: &[Suggestion]) -> Weight {
         use std::cmp::Ordering::{Equal, Less};
@@ -506,106 +747,659 @@ where
         false
     }
 
-    pub(crate) fn suggest(&self) -> Vec<Suggestion> {
-        let pool = Pool::with_size_and_max(self.config.node_pool_size, self.config.node_pool_size);
-        let mut nodes = speller_start_node(&pool, self.state_size() as usize);
-        let mut corrections = HashMap::new();
-        let mut suggestions: Vec<Suggestion> = vec![];
-        let mut best_weight = self.config.max_weight.unwrap_or(f32::MAX);
-        let key_table = self.speller.mutator().alphabet().key_table();
-
+    /// All analyses of the input, lightest first. Like the suggestion search, this is
+    /// best-first over the lexicon with duplicate configurations pruned, so epsilon and flag
+    /// cycles cannot keep it going, and it stops at the weight limit, on interruption or
+    /// after `MAX_ITERATIONS` nodes.
+    pub(crate) fn analyze(&self) -> Vec<Analysis> {
+        let max_weight = speller_max_weight(&self.config);
+        let pool = Pool::with_size_and_max(0, 0);
+        let mut queue = BinaryHeap::with_capacity(256);
+        let mut visited = VisitedNodes::new();
+        let mut output_nodes = Vec::with_capacity(256);
+        let mut analyses = HashMap::new();
         let mut iteration_count = 0usize;
+        let lexicon = self.speller.lexicon();
 
-        while let Some(next_node) = nodes.pop() {
-            iteration_count += 1;
+        let start_node = TreeNode::empty(&pool, vec![0; self.state_size()]).detach();
+        queue.push(QueueNode::new(start_node, false));
+
+        while let Some(entry) = queue.pop() {
+            if iteration_count % 1024 == 0 && self.config.is_interrupted() {
+                break;
+            }
 
-            let max_weight = self.update_weight_limit(best_weight, &suggestions);
+            iteration_count += 1;
 
-            if iteration_count >= 10_000_000 {
-                let name: SmolStr = self
-                    .input
-                    .iter()
-                    .map(|s| &*key_table[*s as usize])
-                    .collect();
-                log::warn!("{}: iteration count at {}", name, iteration_count);
-                log::warn!("Node count: {}", nodes.len());
-                log::warn!("Node weight: {}", next_node.weight());
+            if iteration_count >= MAX_ITERATIONS {
+                let name = string_from_symbols(self.mutator_keys(), &self.input);
+                log::warn!("{}: analysis iteration count at {}", name, iteration_count);
                 break;
             }
 
-            if !self.is_under_weight_limit(max_weight, next_node.weight()) {
+            if !self.is_under_weight_limit(max_weight, entry.node.weight()) {
                 continue;
             }
 
-            self.lexicon_epsilons(&pool, max_weight, &next_node, &mut nodes);
-            self.mutator_epsilons(&pool, max_weight, &next_node, &mut nodes);
-
-            if next_node.input_state as usize != self.input.len() {
-                self.consume_input(&pool, max_weight, &next_node, &mut nodes);
+            if !visited.insert(&entry.node) {
                 continue;
             }
 
-            if !self.speller.mutator().is_final(next_node.mutator_state)
-                || !self.speller.lexicon().is_final(next_node.lexicon_state)
+            let next_node = pool.attach(entry.node);
+
+            // Unlike `is_correct`, a final state is not the end of the road here: an analyser
+            // may still emit tags on input epsilons after the last input symbol.
+            if next_node.input_state as usize == self.input.len()
+                && lexicon.is_final(next_node.lexicon_state)
             {
-                continue;
+                let weight =
+                    next_node.weight() + lexicon.final_weight(next_node.lexicon_state).unwrap();
+
+                if self.is_under_weight_limit(max_weight, weight) {
+                    let analysis =
+                        Analysis::from_symbols(self.lexicon_keys(), &next_node.string, weight);
+                    insert_analysis(&mut analyses, analysis);
+                }
             }
 
-            let weight = next_node.weight()
-                + self
-                    .speller
-                    .lexicon()
-                    .final_weight(next_node.lexicon_state)
-                    .unwrap()
-                + self
-                    .speller
-                    .mutator()
-                    .final_weight(next_node.mutator_state)
-                    .unwrap();
+            self.lexicon_epsilons(&pool, max_weight, &next_node, &mut output_nodes);
+            self.lexicon_consume(&pool, max_weight, &next_node, &mut output_nodes);
 
-            if !self.is_under_weight_limit(max_weight, weight) {
-                continue;
+            queue.extend(
+                output_nodes
+                    .drain(..)
+                    .map(|node| QueueNode::new(node.detach(), false)),
+            );
+        }
+
+        let mut analyses: Vec<Analysis> = analyses.into_iter().map(|x| x.1).collect();
+        analyses.sort();
+        analyses
+    }
+
+    /// Records a finished node as a suggestion, returning its index if it is a new one.
+    fn emit_suggestion(
+        &self,
+        node: &TreeNode,
+        suggestions: &mut Vec<Suggestion>,
+        emitted: &mut HashMap<SmolStr, usize>,
+    ) -> Option<usize> {
+        let key_table = self.lexicon_keys();
+
+        let (string, analysis) = if self.config.with_analyses {
+            let analysis = Analysis::from_symbols(key_table, &node.string, node.weight());
+            (
+                string_from_symbols(key_table, &node.surface),
+                Some(analysis),
+            )
+        } else {
+            (string_from_symbols(key_table, &node.string), None)
+        };
+
+        if self.speller.blocklist().is_blocked(&string) {
+            return None;
+        }
+
+        if let Some(&index) = emitted.get(&string) {
+            // Later paths to the same suggestion are heavier, but may still add analyses.
+            if let (Some(analysis), Some(analyses)) = (analysis, &mut suggestions[index].analyses) {
+                if !analyses.iter().any(|x| x.value == analysis.value) {
+                    analyses.push(analysis);
+                }
+            }
+
+            return None;
+        }
+
+        if let Some(n) = self.config.n_best {
+            if suggestions.len() >= n {
+                return None;
             }
+        }
+
+        let mut suggestion = Suggestion::new(string.clone(), node.weight());
+        suggestion.analyses = analysis.map(|x| vec![x]);
+
+        if self.config.with_edits {
+            suggestion.edits = Some(self.suggestion_edits(&node.edits));
+        }
+
+        emitted.insert(string, suggestions.len());
+        suggestions.push(suggestion);
+        Some(suggestions.len() - 1)
+    }
 
-            let string = self
-                .speller
-                .lexicon()
-                .alphabet()
-                .string_from_symbols(&next_node.string);
+    /// Records a suggestion found outside the search, returning its index if it is a new one.
+    fn emit_extra(
+        &self,
+        suggestion: Suggestion,
+        suggestions: &mut Vec<Suggestion>,
+        emitted: &mut HashMap<SmolStr, usize>,
+    ) -> Option<usize> {
+        if emitted.contains_key(&suggestion.value)
+            || self.speller.blocklist().is_blocked(suggestion.value())
+        {
+            return None;
+        }
 
-            if weight < best_weight {
-                best_weight = weight;
+        if let Some(n) = self.config.n_best {
+            if suggestions.len() >= n {
+                return None;
             }
+        }
 
-            {
-                let entry = corrections.entry(string).or_insert(weight);
+        emitted.insert(suggestion.value.clone(), suggestions.len());
+        suggestions.push(suggestion);
+        Some(suggestions.len() - 1)
+    }
+
+    /// Turns the mutator arcs of a path into edits, merging adjacent swapped substitutions
+    /// into transpositions.
+    fn suggestion_edits(&self, path: &[MutatorEdit]) -> Vec<Edit> {
+        let key_table = self.mutator_keys();
+        let mut edits: Vec<Edit> = Vec::with_capacity(path.len());
+        let mut offset = 0isize;
+
+        for step in path.iter() {
+            let input_index = step.input_state as usize;
+            let output_index = (input_index as isize + offset) as usize;
+            let input = key_table[step.input as usize].clone();
+            let output = key_table[step.output as usize].clone();
+
+            let kind = match (step.input, step.output) {
+                (0, _) => {
+                    offset += 1;
+                    EditKind::Insertion
+                }
+                (_, 0) => {
+                    offset -= 1;
+                    EditKind::Deletion
+                }
+                _ => EditKind::Substitution,
+            };
 
-                if *entry > weight {
-                    *entry = weight;
+            if let Some(last) = edits.last_mut() {
+                if kind == EditKind::Substitution
+                    && last.kind == EditKind::Substitution
+                    && last.input_index + 1 == input_index
+                    && last.output_index + 1 == output_index
+                    && last.input == output
+                    && last.output == input
+                {
+                    last.kind = EditKind::Transposition;
+                    last.input = format!("{}{}", last.input, input).into();
+                    last.output = format!("{}{}", last.output, output).into();
+                    last.weight += step.weight;
+                    continue;
                 }
             }
 
-            suggestions = self.generate_sorted_suggestions(&corrections);
+            edits.push(Edit {
+                kind,
+                input_index,
+                output_index,
+                input,
+                output,
+                weight: step.weight,
+            });
         }
 
-        suggestions
+        edits
     }
+}
 
-    fn generate_sorted_suggestions(
-        &self,
-        corrections: &HashMap<SmolStr, Weight>,
-    ) -> Vec<Suggestion> {
-        let mut c: Vec<Suggestion> = corrections
-            .into_iter()
-            .map(|x| Suggestion::new(x.0.clone(), *x.1))
-            .collect();
+/// A best-first suggestion search that can be paused after each new suggestion.
+pub(crate) struct SuggestionSearch<F: crate::vfs::File, T: Transducer<F>, U: Transducer<F>> {
+    worker: SpellerWorker<F, T, U>,
+    pool: Pool<TreeNode>,
+    queue: BinaryHeap<QueueNode>,
+    visited: VisitedNodes,
+    emitted: HashMap<SmolStr, usize>,
+    suggestions: Vec<Suggestion>,
+    /// Suggestions found outside the search, heaviest first, merged in by weight.
+    extra: Vec<Suggestion>,
+    best_weight: Weight,
+    iteration_count: usize,
+    is_partial: bool,
+}
+
+impl<F, T, U> SuggestionSearch<F, T, U>
+where
+    F: crate::vfs::File,
+    T: Transducer<F>,
+    U: Transducer<F>,
+{
+    pub(crate) fn new(
+        worker: SpellerWorker<F, T, U>,
+        extra: SuggestionResult,
+    ) -> SuggestionSearch<F, T, U> {
+        let SuggestionResult {
+            suggestions: mut extra,
+            is_partial,
+        } = extra;
+        extra.sort_by(|a, b| b.cmp(a));
+
+        let pool_size = worker.config.node_pool_size;
+        let pool = Pool::with_size_and_max(pool_size, pool_size);
+        let mut queue = BinaryHeap::with_capacity(256);
+
+        let start_node = TreeNode::empty(&pool, vec![0; worker.state_size()]).detach();
+        queue.push(QueueNode::new(start_node, false));
+
+        SuggestionSearch {
+            best_weight: speller_max_weight(&worker.config),
+            worker,
+            pool,
+            queue,
+            visited: VisitedNodes::new(),
+            emitted: HashMap::new(),
+            suggestions: vec![],
+            extra,
+            iteration_count: 0,
+            is_partial,
+        }
+    }
+
+    /// Whether the search was cut short by an interruption or the iteration limit.
+    pub(crate) fn is_partial(&self) -> bool {
+        self.is_partial
+    }
 
-        c.sort();
+    /// Runs the search until it finds a new suggestion, returning its index in
+    /// `self.suggestions`, or `None` once the search is over.
+    fn advance(&mut self) -> Option<usize> {
+        let worker = &self.worker;
+        let pool = &self.pool;
+        let mut output_nodes = Vec::with_capacity(256);
 
-        if let Some(n) = self.config.n_best {
-            c.truncate(n);
+        loop {
+            let next_weight = self.queue.peek().map(|x| x.node.weight());
+
+            if let Some(extra) = self.extra.last() {
+                if next_weight.map(|w| extra.weight() <= w).unwrap_or(true) {
+                    let extra = self.extra.pop().unwrap();
+                    let max_weight =
+                        worker.update_weight_limit(self.best_weight, &self.suggestions);
+
+                    if !worker.is_under_weight_limit(max_weight, extra.weight()) {
+                        break;
+                    }
+
+                    if self.suggestions.is_empty() {
+                        self.best_weight = extra.weight();
+                    }
+
+                    let index = worker.emit_extra(extra, &mut self.suggestions, &mut self.emitted);
+
+                    if index.is_some() {
+                        return index;
+                    }
+
+                    continue;
+                }
+            }
+
+            let entry = match self.queue.pop() {
+                Some(entry) => entry,
+                None => break,
+            };
+
+            // Checking the clock is not free, so only do it every so often.
+            if self.iteration_count % 1024 == 0 && worker.config.is_interrupted() {
+                self.is_partial = true;
+                break;
+            }
+
+            self.iteration_count += 1;
+
+            let max_weight = worker.update_weight_limit(self.best_weight, &self.suggestions);
+
+            if self.iteration_count >= MAX_ITERATIONS {
+                let name = string_from_symbols(worker.mutator_keys(), &worker.input);
+                log::warn!("{}: iteration count at {}", name, self.iteration_count);
+                log::warn!("Node count: {}", self.queue.len());
+                log::warn!("Node weight: {}", entry.node.weight());
+                self.is_partial = true;
+                break;
+            }
+
+            // Everything left in the queue weighs at least as much as this node, and the
+            // limit only ever tightens, so nothing after it can be a suggestion. This relies
+            // on no arc weighing less than zero, so that paths only get heavier.
+            if !worker.is_under_weight_limit(max_weight, entry.node.weight()) {
+                break;
+            }
+
+            if entry.is_final {
+                if self.suggestions.is_empty() {
+                    self.best_weight = entry.node.weight();
+                }
+
+                let index =
+                    worker.emit_suggestion(&entry.node, &mut self.suggestions, &mut self.emitted);
+
+                if index.is_some() {
+                    return index;
+                }
+
+                continue;
+            }
+
+            // Nodes leave the queue lightest first, so a configuration seen before was
+            // reached more cheaply and this node can only lead to heavier duplicates.
+            if !self.visited.insert(&entry.node) {
+                continue;
+            }
+
+            let next_node = pool.attach(entry.node);
+
+            worker.lexicon_epsilons(pool, max_weight, &next_node, &mut output_nodes);
+            worker.mutator_epsilons(pool, max_weight, &next_node, &mut output_nodes);
+
+            if next_node.input_state as usize != worker.input.len() {
+                worker.consume_input(pool, max_weight, &next_node, &mut output_nodes);
+            } else if worker.speller.mutator().is_final(next_node.mutator_state)
+                && worker.speller.lexicon().is_final(next_node.lexicon_state)
+            {
+                let weight = next_node.weight()
+                    + worker
+                        .speller
+                        .lexicon()
+                        .final_weight(next_node.lexicon_state)
+                        .unwrap()
+                    + worker
+                        .speller
+                        .mutator()
+                        .final_weight(next_node.mutator_state)
+                        .unwrap();
+
+                // Final weights may be heavier than anything still in the queue, so the
+                // finished node goes back in line rather than being emitted directly.
+                if worker.is_under_weight_limit(max_weight, weight) {
+                    let mut final_node = pool.new_from(&*next_node);
+                    final_node.weight = weight;
+                    self.queue.push(QueueNode::new(final_node.detach(), true));
+                }
+            }
+
+            self.queue.extend(
+                output_nodes
+                    .drain(..)
+                    .map(|node| QueueNode::new(node.detach(), false)),
+            );
+        }
+
+        self.queue.clear();
+        self.extra.clear();
+        None
+    }
+
+    /// Runs the search to the end, returning every suggestion found.
+    pub(crate) fn finish(mut self) -> SuggestionResult {
+        while self.advance().is_some() {}
+
+        let mut suggestions = self.suggestions;
+
+        // Finished nodes come out in weight order, so this only settles ties.
+        suggestions.sort();
+        for analyses in suggestions.iter_mut().filter_map(|x| x.analyses.as_mut()) {
+            analyses.sort();
+        }
+
+        SuggestionResult {
+            suggestions,
+            is_partial: self.is_partial,
+        }
+    }
+}
+
+impl<F, T, U> Iterator for SuggestionSearch<F, T, U>
+where
+    F: crate::vfs::File,
+    T: Transducer<F>,
+    U: Transducer<F>,
+{
+    type Item = Suggestion;
+
+    fn next(&mut self) -> Option<Suggestion> {
+        self.advance().map(|index| self.suggestions[index].clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::speller::testing::TestSpeller;
+    use crate::transducer::testing::{self, TransducerBuilder};
+
+    fn analyses(speller: &Arc<TestSpeller>, word: &str, config: SpellerConfig) -> Vec<SmolStr> {
+        let worker = SpellerWorker::new(speller.clone(), speller.to_input_vec(word), config);
+        worker.analyze().into_iter().map(|x| x.value).collect()
+    }
+
+    #[test]
+    fn identity_arcs_copy_inserted_symbols() {
+        // The lexicon takes any one symbol it does not know between "a" and "b", which only
+        // an insertion of "c" by the mutator can give.
+        let mut lexicon = TransducerBuilder::new();
+        let a = lexicon.state();
+        let any = lexicon.state();
+        let b = lexicon.state();
+        lexicon
+            .arc(0, "a", "a", a, 0.0)
+            .arc(a, testing::IDENTITY, testing::IDENTITY, any, 0.0)
+            .arc(any, "b", "b", b, 0.0)
+            .final_state(b, 0.0);
+        let speller = Speller::new(testing::edit_distance("abc", 1.0), lexicon.build());
+
+        for &with_analyses in [false, true].iter() {
+            let config = SpellerConfig {
+                with_analyses,
+                ..SpellerConfig::default()
+            };
+            let suggestions = speller.clone().suggest_with_config("ab", &config);
+
+            assert_eq!(suggestions.len(), 1);
+            assert_eq!(suggestions[0].value(), "acb");
+
+            if with_analyses {
+                assert_eq!(suggestions[0].analyses.as_ref().unwrap()[0].value, "acb");
+            }
+        }
+    }
+
+    #[test]
+    fn suggestion_edits() {
+        use crate::speller::suggestion::EditKind::*;
+
+        let speller = crate::speller::testing::speller(&[("abc", 0.0)]);
+        let config = SpellerConfig {
+            with_edits: true,
+            ..SpellerConfig::default()
+        };
+        let edits = |word: &str| {
+            let suggestions = speller.clone().suggest_with_config(word, &config);
+            assert_eq!(suggestions[0].value(), "abc");
+            suggestions[0].edits().unwrap().to_vec()
+        };
+        let edit = |kind, input_index, output_index, input: &str, output: &str| Edit {
+            kind,
+            input_index,
+            output_index,
+            input: input.into(),
+            output: output.into(),
+            weight: 1.0,
+        };
+
+        assert_eq!(edits("ac"), vec![edit(Insertion, 1, 1, "", "b")]);
+        assert_eq!(edits("abac"), vec![edit(Deletion, 2, 2, "a", "")]);
+        assert_eq!(edits("aac"), vec![edit(Substitution, 1, 1, "a", "b")]);
+        // The error model swaps letters with two substitutions, which become one edit.
+        assert_eq!(edits("bac"), vec![edit(Transposition, 0, 0, "ba", "ab")]);
+    }
+
+    #[test]
+    fn foreign_characters() {
+        use crate::speller::suggestion::EditKind::*;
+        use testing::IDENTITY;
+
+        // One edit over "abc", where any symbol outside it may be deleted.
+        let mut mutator = TransducerBuilder::new();
+        let edited = mutator.state();
+        mutator
+            .final_state(0, 0.0)
+            .final_state(edited, 0.0)
+            .arc(0, IDENTITY, IDENTITY, 0, 0.0)
+            .arc(edited, IDENTITY, IDENTITY, edited, 0.0)
+            .arc(0, IDENTITY, "", edited, 1.0);
+
+        for a in ["a", "b", "c"].iter() {
+            mutator.arc(0, a, a, 0, 0.0).arc(edited, a, a, edited, 0.0);
+
+            for b in ["a", "b", "c"].iter().filter(|b| *b != a) {
+                mutator.arc(0, a, b, edited, 1.0);
+            }
         }
 
-        c
+        // "ab" with any unknown symbols before and after it.
+        let mut lexicon = TransducerBuilder::new();
+        let a = lexicon.state();
+        let b = lexicon.state();
+        lexicon
+            .arc(0, IDENTITY, IDENTITY, 0, 0.0)
+            .arc(0, "a", "a", a, 0.0)
+            .arc(a, "b", "b", b, 0.0)
+            .arc(b, IDENTITY, IDENTITY, b, 0.0)
+            .final_state(b, 0.0);
+
+        let speller = Speller::new(mutator.build(), lexicon.build());
+        let config = SpellerConfig {
+            with_edits: true,
+            ..SpellerConfig::default()
+        };
+        let suggest = |word: &str| {
+            let suggestions = speller.clone().suggest_with_config(word, &config);
+            let edits = suggestions[0].edits().unwrap().to_vec();
+            (suggestions[0].value().to_string(), edits)
+        };
+        let edit = |kind, index, input: &str, output: &str| Edit {
+            kind,
+            input_index: index,
+            output_index: index,
+            input: input.into(),
+            output: output.into(),
+            weight: 1.0,
+        };
+
+        assert!(speller.clone().is_correct("12ab😀"));
+        assert_eq!(
+            suggest("7😀ac"),
+            ("7😀ab".into(), vec![edit(Substitution, 3, "c", "b")])
+        );
+        assert_eq!(
+            suggest("ac7😀"),
+            ("ab7😀".into(), vec![edit(Substitution, 1, "c", "b")])
+        );
+        assert_eq!(
+            suggest("a7b😀"),
+            ("ab😀".into(), vec![edit(Deletion, 1, "7", "")])
+        );
+
+        // Foreign characters were once dropped from the input, accepting any word with them
+        // added.
+        let speller = crate::speller::testing::speller(&[("abc", 0.0)]);
+        assert!(speller.clone().is_correct("abc"));
+        assert!(!speller.clone().is_correct("abc😀"));
+        assert!(!speller.is_correct("a7bc"));
+    }
+
+    #[test]
+    fn interrupted_search_is_partial() {
+        let speller = crate::speller::testing::speller(&[("abc", 0.0)]);
+
+        let result = speller
+            .clone()
+            .suggest_with_result("aac", &SpellerConfig::default());
+        assert!(!result.is_partial);
+        assert_eq!(result.suggestions[0].value(), "abc");
+
+        let config = SpellerConfig {
+            deadline: Some(std::time::Instant::now()),
+            ..SpellerConfig::default()
+        };
+        let result = speller.clone().suggest_with_result("aac", &config);
+        assert!(result.is_partial);
+        assert!(result.suggestions.is_empty());
+
+        let config = SpellerConfig {
+            cancel: Some(Arc::new(std::sync::atomic::AtomicBool::new(true))),
+            ..SpellerConfig::default()
+        };
+        let result = speller.clone().suggest_with_result("aac", &config);
+        assert!(result.is_partial);
+        assert!(result.suggestions.is_empty());
+    }
+
+    #[test]
+    fn search_stops_at_the_limit() {
+        let speller = crate::speller::testing::speller(&[
+            ("abc", 0.0),
+            ("abd", 1.0),
+            ("ab", 2.0),
+            ("bcd", 0.0),
+        ]);
+
+        let config = SpellerConfig::default();
+        let weights: Vec<Weight> = speller
+            .clone()
+            .suggestion_search("abb", &config)
+            .map(|x| x.weight())
+            .collect();
+        assert_eq!(weights, vec![1.0, 2.0, 3.0]);
+
+        // Once the one suggestion asked for is found, the search ends at the first node
+        // over its weight, leaving the rest of the queue unvisited.
+        let config = SpellerConfig {
+            n_best: Some(1),
+            ..SpellerConfig::default()
+        };
+        let mut search = speller.suggestion_search("abc", &config);
+        assert_eq!(search.next().unwrap().value(), "abc");
+
+        let (iterations, queued) = (search.iteration_count, search.queue.len());
+        assert!(search.next().is_none());
+        assert!(search.iteration_count - iterations < queued);
+        assert!(!search.is_partial());
+    }
+
+    #[test]
+    fn analysis_ends_on_cycles() {
+        // "a" loops back on itself through epsilons, and can take any number of "+Foc" tags.
+        let mut lexicon = TransducerBuilder::new();
+        let a = lexicon.state();
+        let b = lexicon.state();
+        lexicon
+            .arc(0, "a", "a", a, 0.0)
+            .arc(a, "", "", b, 0.0)
+            .arc(b, "", "", a, 0.0)
+            .arc(a, "", "+Foc", a, 1.0)
+            .final_state(a, 0.0);
+        let speller = Speller::new(testing::edit_distance("a", 1.0), lexicon.build());
+
+        let config = SpellerConfig {
+            max_weight: Some(2.5),
+            ..SpellerConfig::default()
+        };
+        assert_eq!(
+            analyses(&speller, "a", config),
+            vec!["a", "a+Foc", "a+Foc+Foc"]
+        );
+
+        let cancel = Arc::new(std::sync::atomic::AtomicBool::new(true));
+        let config = SpellerConfig {
+            cancel: Some(cancel),
+            ..SpellerConfig::default()
+        };
+        assert!(analyses(&speller, "a", config).is_empty());
     }
 }
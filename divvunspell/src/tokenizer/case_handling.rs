@@ -1,5 +1,7 @@
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 use smol_str::SmolStr;
+use unic_segment::Graphemes;
 
 #[inline(always)]
 pub fn lower_case(s: &str) -> SmolStr {
@@ -51,6 +53,15 @@ impl Case {
             Case::Neither
         }
     }
+
+    /// The case of the first cased character of a grapheme cluster.
+    fn of_cluster(cluster: &str) -> Case {
+        cluster
+            .chars()
+            .map(Case::new)
+            .find(|x| *x != Case::Neither)
+            .unwrap_or(Case::Neither)
+    }
 }
 
 pub fn is_mixed_case(word: &str) -> bool {
@@ -90,26 +101,333 @@ pub fn is_first_caps(word: &str) -> bool {
     upper_first(word) == word
 }
 
-#[derive(Debug, PartialEq, Eq)]
+/// Capitalizes every hyphen-separated part of a word, e.g. "Sámi-English" for "SÁMI-ENGLISH".
+pub fn title_case(word: &str) -> SmolStr {
+    CaseMapping::Default.title_case(word)
+}
+
+/// Combining marks that sit above their base letter, which in Lithuanian keep the dot of a
+/// lowercase i or j. Only the accents used in Lithuanian dictionaries are covered.
+#[inline(always)]
+fn is_accent_above(ch: char) -> bool {
+    ('\u{300}'..='\u{314}').contains(&ch)
+}
+
+/// Case conversion for a language, where it differs from the Unicode default mappings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CaseMapping {
+    Default,
+    /// Turkish and Azerbaijani, where dotted İ/i and dotless I/ı are distinct letters.
+    Turkic,
+    /// Lithuanian, where lowercase i and j keep their dot under an accent.
+    Lithuanian,
+    /// Dutch, where a word-initial "ij" is capitalized as a whole, as in "IJsselmeer".
+    Dutch,
+}
+
+impl CaseMapping {
+    /// The mapping for a locale, such as `tr` or `nl-BE`.
+    pub fn from_locale(locale: &str) -> CaseMapping {
+        let language = locale
+            .split(&['-', '_'][..])
+            .next()
+            .unwrap_or("")
+            .to_ascii_lowercase();
+
+        match &*language {
+            "tr" | "az" => CaseMapping::Turkic,
+            "lt" => CaseMapping::Lithuanian,
+            "nl" => CaseMapping::Dutch,
+            _ => CaseMapping::Default,
+        }
+    }
+
+    pub fn lower_case(self, s: &str) -> SmolStr {
+        let mut out = String::with_capacity(s.len());
+        let mut chars = s.chars().peekable();
+
+        while let Some(ch) = chars.next() {
+            match (self, ch) {
+                (CaseMapping::Turkic, 'I') if chars.peek() == Some(&'\u{307}') => {
+                    chars.next();
+                    out.push('i');
+                }
+                (CaseMapping::Turkic, 'I') => out.push('ı'),
+                (CaseMapping::Turkic, 'İ') => out.push('i'),
+                (CaseMapping::Lithuanian, 'Ì') => out.push_str("i\u{307}\u{300}"),
+                (CaseMapping::Lithuanian, 'Í') => out.push_str("i\u{307}\u{301}"),
+                (CaseMapping::Lithuanian, 'Ĩ') => out.push_str("i\u{307}\u{303}"),
+                (CaseMapping::Lithuanian, 'I')
+                | (CaseMapping::Lithuanian, 'J')
+                | (CaseMapping::Lithuanian, 'Į')
+                    if chars.peek().copied().map(is_accent_above).unwrap_or(false) =>
+                {
+                    out.extend(ch.to_lowercase());
+                    out.push('\u{307}');
+                }
+                _ => out.extend(ch.to_lowercase()),
+            }
+        }
+
+        out.into()
+    }
+
+    pub fn upper_case(self, s: &str) -> SmolStr {
+        let mut out = String::with_capacity(s.len());
+        let mut last = None;
+
+        for ch in s.chars() {
+            match (self, ch) {
+                (CaseMapping::Turkic, 'i') => out.push('İ'),
+                (CaseMapping::Lithuanian, '\u{307}')
+                    if last == Some('i') || last == Some('j') || last == Some('į') => {}
+                _ => out.extend(ch.to_uppercase()),
+            }
+
+            last = Some(ch);
+        }
+
+        out.into()
+    }
+
+    /// The length in bytes of what counts as the first letter of `s` when capitalizing,
+    /// which is at least its first grapheme cluster.
+    fn first_len(self, s: &str) -> usize {
+        let mut chars = s.char_indices();
+        let first = match chars.next() {
+            Some((_, ch)) => ch,
+            None => return 0,
+        };
+
+        match self {
+            CaseMapping::Dutch
+                if (first == 'i' || first == 'I')
+                    && chars
+                        .next()
+                        .map(|(_, x)| x == 'j' || x == 'J')
+                        .unwrap_or(false) =>
+            {
+                2
+            }
+            CaseMapping::Lithuanian => chars
+                .find(|(_, x)| !is_accent_above(*x))
+                .map(|(i, _)| i)
+                .unwrap_or_else(|| s.len()),
+            _ => Graphemes::new(s).next().map(|x| x.len()).unwrap_or(0),
+        }
+    }
+
+    pub fn upper_first(self, s: &str) -> SmolStr {
+        let (first, rest) = s.split_at(self.first_len(s));
+        SmolStr::from(self.upper_case(first).to_string() + rest)
+    }
+
+    pub fn lower_first(self, s: &str) -> SmolStr {
+        let (first, rest) = s.split_at(self.first_len(s));
+        SmolStr::from(self.lower_case(first).to_string() + rest)
+    }
+
+    pub fn is_all_caps(self, word: &str) -> bool {
+        self.upper_case(word) == word
+    }
+
+    pub fn is_first_caps(self, word: &str) -> bool {
+        self.upper_first(word) == word
+    }
+
+    /// Capitalizes every hyphen-separated part of a word.
+    pub fn title_case(self, word: &str) -> SmolStr {
+        word.split('-')
+            .map(|part| self.upper_first(&self.lower_case(part)))
+            .join("-")
+            .into()
+    }
+
+    /// Like the free function `apply_case_pattern`, using this mapping.
+    pub fn apply_case_pattern(
+        self,
+        pattern: &str,
+        value: &str,
+        alignment: &[Option<usize>],
+    ) -> SmolStr {
+        let pattern: Vec<Case> = Graphemes::new(pattern).map(Case::of_cluster).collect();
+        let case_at = |index: Option<&Option<usize>>| {
+            index
+                .copied()
+                .flatten()
+                .and_then(|x| pattern.get(x).copied())
+        };
+
+        let mut out = String::with_capacity(value.len());
+
+        for (index, cluster) in Graphemes::new(value).enumerate() {
+            let case = match case_at(alignment.get(index)) {
+                Some(case) => case,
+                None => {
+                    let before = alignment[..index.min(alignment.len())]
+                        .iter()
+                        .rev()
+                        .find_map(|x| case_at(Some(x)));
+                    let after = alignment
+                        .iter()
+                        .skip(index + 1)
+                        .find_map(|x| case_at(Some(x)));
+
+                    match (before, after) {
+                        (Some(Case::Upper), Some(Case::Upper))
+                        | (Some(Case::Upper), None)
+                        | (None, Some(Case::Upper)) => Case::Upper,
+                        (None, None) => Case::Neither,
+                        _ => Case::Lower,
+                    }
+                }
+            };
+
+            match case {
+                Case::Upper => out.push_str(&self.upper_case(cluster)),
+                Case::Lower => out.push_str(&self.lower_case(cluster)),
+                Case::Neither => out.push_str(cluster),
+            }
+        }
+
+        out.into()
+    }
+
+    /// Like the free function `word_variants`, using this mapping.
+    pub fn word_variants(self, word: &str) -> CaseHandler {
+        if is_mixed_case(word) {
+            return mixed_case_word_variants(word, self);
+        }
+
+        let mut base = vec![SmolStr::new(word)];
+
+        base.append(
+            &mut base
+                .iter()
+                .filter(|x| self.is_all_caps(x))
+                .map(|x| self.upper_first(&self.lower_case(x)))
+                .collect(),
+        );
+
+        base.append(&mut base.iter().map(|x| self.lower_case(x)).collect());
+
+        let mut words = vec![];
+
+        for b in base.into_iter() {
+            if !words.contains(&b) {
+                words.push(b);
+            }
+        }
+
+        let (mutation, mode) = if self.is_first_caps(word) {
+            (CaseMutation::FirstCaps, CaseMode::MergeAll)
+        } else if self.is_all_caps(word) {
+            (CaseMutation::AllCaps, CaseMode::MergeAll)
+        } else {
+            (CaseMutation::None, CaseMode::MergeAll)
+        };
+
+        CaseHandler {
+            mode,
+            mutation,
+            words,
+        }
+    }
+}
+
+/// Aligns the characters of `value` with those of `pattern` by edit distance, ignoring
+/// case. Characters are grapheme clusters, so a letter and its combining marks are one.
+/// Each character of `value` maps to the index of the character of `pattern` it matches or
+/// replaces, or to `None` if it was inserted.
+pub fn align_chars(pattern: &str, value: &str) -> Vec<Option<usize>> {
+    let a: Vec<SmolStr> = Graphemes::new(pattern).map(lower_case).collect();
+    let b: Vec<SmolStr> = Graphemes::new(value).map(lower_case).collect();
+    let cols = b.len() + 1;
+    let mut dist = vec![0usize; (a.len() + 1) * cols];
+
+    for i in 0..=a.len() {
+        for j in 0..=b.len() {
+            dist[i * cols + j] = if i == 0 || j == 0 {
+                i + j
+            } else {
+                let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+                (dist[(i - 1) * cols + j - 1] + cost)
+                    .min(dist[(i - 1) * cols + j] + 1)
+                    .min(dist[i * cols + j - 1] + 1)
+            };
+        }
+    }
+
+    let mut alignment = vec![None; b.len()];
+    let (mut i, mut j) = (a.len(), b.len());
+
+    while i > 0 && j > 0 {
+        let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+
+        if dist[i * cols + j] == dist[(i - 1) * cols + j - 1] + cost {
+            alignment[j - 1] = Some(i - 1);
+            i -= 1;
+            j -= 1;
+        } else if dist[i * cols + j] == dist[(i - 1) * cols + j] + 1 {
+            i -= 1;
+        } else {
+            j -= 1;
+        }
+    }
+
+    alignment
+}
+
+/// Gives each character of `value`, as grapheme clusters, the case of the character of
+/// `pattern` it is aligned with. Inserted characters are upper case only if their aligned
+/// neighbours are. Characters aligned with caseless ones are left as they are.
+pub fn apply_case_pattern(pattern: &str, value: &str, alignment: &[Option<usize>]) -> SmolStr {
+    CaseMapping::Default.apply_case_pattern(pattern, value, alignment)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum CaseMutation {
     FirstCaps,
     AllCaps,
+    /// Follow the case of the input character by character, for mixed-case input.
+    Mixed(SmolStr),
     None,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+/// How the suggestions for the case variants of a word are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CaseMode {
+    /// Only the suggestions of the first variant that has any.
     FirstResults,
+    /// The suggestions of all variants, penalized by how far each variant is from the
+    /// input.
     MergeAll,
+    /// The suggestions of all variants, on their own weights.
+    MergeUnpenalized,
 }
 
+#[derive(Debug, Clone)]
 pub struct CaseHandler {
     pub mutation: CaseMutation,
     pub mode: CaseMode,
     pub words: Vec<SmolStr>,
 }
 
-fn mixed_case_word_variants(word: &str) -> CaseHandler {
+/// Decides which spellings of a word are looked up, when checking it and when suggesting
+/// for it. Set one with `SpellerConfig::case_policy` to replace the built-in handling, which
+/// `CaseHandlingConfig` implements.
+///
+/// `mapping` is the case mapping for the speller's locale.
+pub trait CasePolicy: std::fmt::Debug + Send + Sync {
+    /// Spellings of `word` to check, in order. The word is accepted if any of them is.
+    fn accept_variants(&self, word: &str, mapping: CaseMapping) -> Vec<SmolStr>;
+
+    /// Spellings of `word` to suggest for, how to recase the suggestions and how to merge
+    /// the suggestions of the different spellings.
+    fn suggest_variants(&self, word: &str, mapping: CaseMapping) -> CaseHandler;
+}
+
+fn mixed_case_word_variants(word: &str, mapping: CaseMapping) -> CaseHandler {
     // The input string should be accepted IFF it is accepted exactly as given,
     // or with the initial letter downcased, or all upper.
     //
@@ -117,59 +435,23 @@ fn mixed_case_word_variants(word: &str) -> CaseHandler {
 
     let words = vec![
         word.into(),
-        upper_first(word),
-        lower_first(word),
-        upper_case(word),
+        mapping.upper_first(word),
+        mapping.lower_first(word),
+        mapping.upper_case(word),
     ]
     .into_iter()
     .unique()
     .collect();
 
     CaseHandler {
-        mutation: CaseMutation::None,
+        mutation: CaseMutation::Mixed(word.into()),
         mode: CaseMode::FirstResults,
         words,
     }
 }
 
 pub fn word_variants(word: &str) -> CaseHandler {
-    if is_mixed_case(word) {
-        return mixed_case_word_variants(word);
-    }
-
-    let mut base = vec![SmolStr::new(word)];
-
-    base.append(
-        &mut base
-            .iter()
-            .filter(|x| is_all_caps(x))
-            .map(|x| upper_first(&lower_case(x)))
-            .collect(),
-    );
-
-    base.append(&mut base.iter().map(|x| lower_case(x)).collect());
-
-    let mut words = vec![];
-
-    for b in base.into_iter() {
-        if !words.contains(&b) {
-            words.push(b);
-        }
-    }
-
-    let (mutation, mode) = if is_first_caps(word) {
-        (CaseMutation::FirstCaps, CaseMode::MergeAll)
-    } else if is_all_caps(word) {
-        (CaseMutation::AllCaps, CaseMode::MergeAll)
-    } else {
-        (CaseMutation::None, CaseMode::MergeAll)
-    };
-
-    CaseHandler {
-        mode,
-        mutation,
-        words,
-    }
+    CaseMapping::Default.word_variants(word)
 }
 
 #[cfg(test)]
@@ -216,4 +498,61 @@ mod tests {
         assert_eq!(is_mixed_case("SGPaiSGP"), true);
         assert_eq!(is_mixed_case("sgpAI"), true);
     }
+
+    #[test]
+    fn case_pattern() {
+        let recase = |pattern: &str, value: &str| {
+            apply_case_pattern(pattern, value, &align_chars(pattern, value))
+        };
+
+        assert_eq!(recase("McDONLAD", "McDonald"), "McDONALD");
+        assert_eq!(recase("iPHNOE", "iphone"), "iPHONE");
+        assert_eq!(recase("McDONAD", "mcdonald"), "McDONALD");
+        assert_eq!(recase("iPHON", "iphone"), "iPHONE");
+        assert_eq!(recase("McDonlad", "mcdonald"), "McDonald");
+    }
+
+    #[test]
+    fn locale_mappings() {
+        let turkic = CaseMapping::from_locale("tr-TR");
+        assert_eq!(turkic.upper_case("istanbul"), "İSTANBUL");
+        assert_eq!(turkic.lower_case("DIŞ"), "dış");
+        assert_eq!(
+            turkic.word_variants("İZMİR").words,
+            vec!["İZMİR", "İzmir", "izmir"]
+        );
+
+        let lithuanian = CaseMapping::from_locale("lt");
+        assert_eq!(lithuanian.lower_case("ĨS"), "i\u{307}\u{303}s");
+        assert_eq!(lithuanian.upper_case("i\u{307}\u{303}s"), "I\u{303}S");
+
+        let dutch = CaseMapping::from_locale("nl_NL");
+        assert_eq!(dutch.upper_first("ijsselmeer"), "IJsselmeer");
+        assert_eq!(
+            dutch.word_variants("IJSSELMEER").words,
+            vec!["IJSSELMEER", "IJsselmeer", "ijsselmeer"]
+        );
+        assert_eq!(
+            dutch.word_variants("IJsselmeer").mutation,
+            CaseMutation::FirstCaps
+        );
+    }
+
+    #[test]
+    fn title_case_parts() {
+        assert_eq!(title_case("SÁMI-ENGLISH"), "Sámi-English");
+        assert_eq!(title_case("oslo"), "Oslo");
+    }
+
+    #[test]
+    fn grapheme_clusters() {
+        let alignment = align_chars("A\u{301}B", "a\u{301}xb");
+        assert_eq!(alignment, vec![Some(0), None, Some(1)]);
+        assert_eq!(
+            apply_case_pattern("A\u{301}B", "a\u{301}xb", &alignment),
+            "A\u{301}XB"
+        );
+        assert_eq!(upper_first("a\u{301}b"), "A\u{301}b");
+        assert_eq!(CaseMapping::Default.upper_first("a\u{301}b"), "A\u{301}b");
+    }
 }
@@ -1,7 +1,7 @@
 use unic_ucd_common::alphanumeric::is_alphanumeric;
 use word::{WordBoundIndices, Words};
 
-pub(crate) mod case_handling;
+pub mod case_handling;
 pub mod word;
 mod word_break;
 
@@ -5,6 +5,9 @@ mod alphabet;
 mod symbol_transition;
 pub(crate) mod tree_node;
 
+#[cfg(test)]
+pub(crate) mod testing;
+
 pub(crate) use self::alphabet::TransducerAlphabet;
 
 use self::symbol_transition::SymbolTransition;
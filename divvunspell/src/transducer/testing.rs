@@ -0,0 +1,248 @@
+//! Small transducers for tests, written out in the HFST optimized-lookup format and mapped
+//! from anonymous memory.
+
+use std::sync::Arc;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use hashbrown::HashMap;
+use memmap::MmapMut;
+use smol_str::SmolStr;
+
+use super::hfst::HfstTransducer;
+use crate::constants::TARGET_TABLE;
+use crate::types::{SymbolNumber, Weight};
+
+pub(crate) const EPSILON: &str = "@_EPSILON_SYMBOL_@";
+pub(crate) const IDENTITY: &str = "@_IDENTITY_SYMBOL_@";
+
+struct Edge {
+    input: SymbolNumber,
+    output: SymbolNumber,
+    target: usize,
+    weight: Weight,
+}
+
+/// Builds a transducer state by state. State 0 is the start state. Every state gets its own
+/// block of the index table, so lookups never go through the transition table alone.
+pub(crate) struct TransducerBuilder {
+    symbols: Vec<SmolStr>,
+    states: Vec<Vec<Edge>>,
+    finals: HashMap<usize, Weight>,
+}
+
+impl TransducerBuilder {
+    pub(crate) fn new() -> TransducerBuilder {
+        TransducerBuilder {
+            symbols: vec![EPSILON.into()],
+            states: vec![vec![]],
+            finals: HashMap::new(),
+        }
+    }
+
+    /// The number of `symbol`, added if new. The empty string is epsilon.
+    pub(crate) fn symbol(&mut self, symbol: &str) -> SymbolNumber {
+        let symbol = if symbol.is_empty() { EPSILON } else { symbol };
+
+        match self.symbols.iter().position(|x| x == symbol) {
+            Some(index) => index as SymbolNumber,
+            None => {
+                self.symbols.push(symbol.into());
+                (self.symbols.len() - 1) as SymbolNumber
+            }
+        }
+    }
+
+    pub(crate) fn state(&mut self) -> usize {
+        self.states.push(vec![]);
+        self.states.len() - 1
+    }
+
+    pub(crate) fn arc(
+        &mut self,
+        from: usize,
+        input: &str,
+        output: &str,
+        target: usize,
+        weight: Weight,
+    ) -> &mut TransducerBuilder {
+        let input = self.symbol(input);
+        let output = self.symbol(output);
+        self.states[from].push(Edge {
+            input,
+            output,
+            target,
+            weight,
+        });
+        self
+    }
+
+    pub(crate) fn final_state(&mut self, state: usize, weight: Weight) -> &mut TransducerBuilder {
+        self.finals.insert(state, weight);
+        self
+    }
+
+    /// Epsilons and flags are looked up together, under the epsilon symbol.
+    fn group(&self, symbol: SymbolNumber) -> SymbolNumber {
+        if self.symbols[symbol as usize].starts_with("@P.")
+            || self.symbols[symbol as usize].starts_with("@R.")
+            || self.symbols[symbol as usize].starts_with("@D.")
+            || self.symbols[symbol as usize].starts_with("@C.")
+            || self.symbols[symbol as usize].starts_with("@N.")
+            || self.symbols[symbol as usize].starts_with("@U.")
+        {
+            0
+        } else {
+            symbol
+        }
+    }
+
+    pub(crate) fn build(&self) -> HfstTransducer<std::fs::File> {
+        let symbol_count = self.symbols.len();
+        let block = symbol_count + 1;
+        let index_size = self.states.len() * block;
+
+        let mut index = vec![(u16::MAX, u32::MAX); index_size];
+        let mut transitions: Vec<(u16, u16, u32, f32)> = vec![];
+
+        for (state, arcs) in self.states.iter().enumerate() {
+            let base = state * block;
+
+            if let Some(weight) = self.finals.get(&state) {
+                index[base] = (u16::MAX, weight.to_bits());
+            }
+
+            for group in 0..symbol_count as SymbolNumber {
+                let group_arcs: Vec<&Edge> = arcs
+                    .iter()
+                    .filter(|x| self.group(x.input) == group)
+                    .collect();
+
+                if group_arcs.is_empty() {
+                    continue;
+                }
+
+                index[base + 1 + group as usize] = (group, TARGET_TABLE + transitions.len() as u32);
+
+                for arc in group_arcs {
+                    transitions.push((
+                        arc.input,
+                        arc.output,
+                        (arc.target * block) as u32,
+                        arc.weight,
+                    ));
+                }
+
+                transitions.push((u16::MAX, u16::MAX, u32::MAX, 0.0));
+            }
+        }
+
+        let mut buf = vec![];
+        buf.extend_from_slice(b"HFST\0");
+        buf.write_u16::<LittleEndian>(0).unwrap();
+        buf.push(0);
+        buf.write_u16::<LittleEndian>(symbol_count as u16).unwrap();
+        buf.write_u16::<LittleEndian>(symbol_count as u16).unwrap();
+        buf.write_u32::<LittleEndian>(index_size as u32).unwrap();
+        buf.write_u32::<LittleEndian>(transitions.len() as u32)
+            .unwrap();
+        buf.write_u32::<LittleEndian>(self.states.len() as u32)
+            .unwrap();
+        buf.write_u32::<LittleEndian>(transitions.len() as u32)
+            .unwrap();
+
+        for property in 0..9 {
+            // Only the first property, weighted, is set.
+            buf.write_u32::<LittleEndian>(if property == 0 { 1 } else { 0 })
+                .unwrap();
+        }
+
+        for symbol in self.symbols.iter() {
+            buf.extend_from_slice(symbol.as_bytes());
+            buf.push(0);
+        }
+
+        for (symbol, target) in index {
+            buf.write_u16::<LittleEndian>(symbol).unwrap();
+            buf.write_u32::<LittleEndian>(target).unwrap();
+        }
+
+        for (input, output, target, weight) in transitions {
+            buf.write_u16::<LittleEndian>(input).unwrap();
+            buf.write_u16::<LittleEndian>(output).unwrap();
+            buf.write_u32::<LittleEndian>(target).unwrap();
+            buf.write_f32::<LittleEndian>(weight).unwrap();
+        }
+
+        let mut mmap = MmapMut::map_anon(buf.len()).unwrap();
+        mmap.copy_from_slice(&buf);
+        HfstTransducer::from_mapped_memory(Arc::new(mmap.make_read_only().unwrap()))
+    }
+}
+
+/// A lexicon of `words` with their weights, as a letter trie. With `identity`, the lexicon
+/// also has a word made of any one symbol it does not know.
+pub(crate) fn lexicon(words: &[(&str, Weight)], identity: bool) -> HfstTransducer<std::fs::File> {
+    let mut builder = TransducerBuilder::new();
+    let mut children: HashMap<(usize, char), usize> = HashMap::new();
+
+    for (word, weight) in words.iter() {
+        let mut state = 0;
+
+        for ch in word.chars() {
+            state = match children.get(&(state, ch)) {
+                Some(&next) => next,
+                None => {
+                    let next = builder.state();
+                    let s = ch.to_string();
+                    builder.arc(state, &s, &s, next, 0.0);
+                    children.insert((state, ch), next);
+                    next
+                }
+            };
+        }
+
+        builder.final_state(state, *weight);
+    }
+
+    if identity {
+        let next = builder.state();
+        builder
+            .arc(0, IDENTITY, IDENTITY, next, 0.0)
+            .final_state(next, 0.0);
+    }
+
+    builder.build()
+}
+
+/// An error model allowing one edit of `weight` over the letters of `alphabet`: a
+/// substitution, insertion, deletion or swap of neighbours. Symbols outside the alphabet
+/// are copied by identity arcs.
+pub(crate) fn edit_distance(alphabet: &str, weight: Weight) -> HfstTransducer<std::fs::File> {
+    let mut builder = TransducerBuilder::new();
+    let edited = builder.state();
+    let letters: Vec<String> = alphabet.chars().map(|x| x.to_string()).collect();
+
+    builder
+        .final_state(0, 0.0)
+        .final_state(edited, 0.0)
+        .arc(0, IDENTITY, IDENTITY, 0, 0.0)
+        .arc(edited, IDENTITY, IDENTITY, edited, 0.0);
+
+    for a in letters.iter() {
+        builder
+            .arc(0, a, a, 0, 0.0)
+            .arc(edited, a, a, edited, 0.0)
+            .arc(0, a, "", edited, weight)
+            .arc(0, "", a, edited, weight);
+
+        for b in letters.iter().filter(|b| *b != a) {
+            let swapped = builder.state();
+            builder
+                .arc(0, a, b, edited, weight)
+                .arc(0, a, b, swapped, 0.0)
+                .arc(swapped, b, a, edited, weight);
+        }
+    }
+
+    builder.build()
+}
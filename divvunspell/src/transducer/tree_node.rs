@@ -1,6 +1,7 @@
+use hashbrown::hash_map::{HashMap, RawEntryMut};
 use lifeguard::{Pool, Recycled};
 use std::cmp::Ordering;
-use std::hash::{Hash, Hasher};
+use std::hash::{BuildHasher, Hash, Hasher};
 
 use super::symbol_transition::SymbolTransition;
 use crate::types::{
@@ -8,6 +9,15 @@ use crate::types::{
     TransitionTableIndex, Weight,
 };
 
+/// A mutator arc that changed the input, recorded when a suggestion's edits are requested.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MutatorEdit {
+    pub input_state: u32,
+    pub input: SymbolNumber,
+    pub output: SymbolNumber,
+    pub weight: Weight,
+}
+
 #[derive(Debug, Clone)]
 pub struct TreeNode {
     pub lexicon_state: TransitionTableIndex,
@@ -16,6 +26,96 @@ pub struct TreeNode {
     pub weight: f32,
     pub flag_state: FlagDiacriticState,
     pub string: Vec<SymbolNumber>,
+    pub surface: Vec<SymbolNumber>,
+    pub edits: Vec<MutatorEdit>,
+}
+
+/// Everything about a node that decides where the search can go from it. Nodes with the
+/// same key differ only in weight and in the path taken to reach them, so only the lightest
+/// of them needs expanding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeKey {
+    input_state: u32,
+    mutator_state: TransitionTableIndex,
+    lexicon_state: TransitionTableIndex,
+    flag_state: FlagDiacriticState,
+    string: Vec<SymbolNumber>,
+    surface: Vec<SymbolNumber>,
+}
+
+#[inline(always)]
+fn hash_key<H: Hasher>(
+    state: &mut H,
+    input_state: u32,
+    mutator_state: TransitionTableIndex,
+    lexicon_state: TransitionTableIndex,
+    flag_state: &[i16],
+    string: &[SymbolNumber],
+    surface: &[SymbolNumber],
+) {
+    state.write_u32(input_state);
+    state.write_u32(mutator_state);
+    state.write_u32(lexicon_state);
+    flag_state.hash(state);
+    string.hash(state);
+    surface.hash(state);
+}
+
+impl Hash for NodeKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash_key(
+            state,
+            self.input_state,
+            self.mutator_state,
+            self.lexicon_state,
+            &self.flag_state,
+            &self.string,
+            &self.surface,
+        );
+    }
+}
+
+/// The configurations a search has expanded. Nodes are looked up by their fields, so a
+/// `NodeKey` is only built for configurations not seen before.
+#[derive(Debug, Default)]
+pub struct VisitedNodes {
+    keys: HashMap<NodeKey, ()>,
+}
+
+impl VisitedNodes {
+    pub fn new() -> VisitedNodes {
+        VisitedNodes::default()
+    }
+
+    /// Marks the configuration of `node` as visited, returning whether it is new.
+    pub fn insert(&mut self, node: &TreeNode) -> bool {
+        let mut hasher = self.keys.hasher().build_hasher();
+        hash_key(
+            &mut hasher,
+            node.input_state,
+            node.mutator_state,
+            node.lexicon_state,
+            &node.flag_state,
+            &node.string,
+            &node.surface,
+        );
+        let hash = hasher.finish();
+
+        match self.keys.raw_entry_mut().from_hash(hash, |key| {
+            key.input_state == node.input_state
+                && key.mutator_state == node.mutator_state
+                && key.lexicon_state == node.lexicon_state
+                && key.flag_state == node.flag_state
+                && key.string == node.string
+                && key.surface == node.surface
+        }) {
+            RawEntryMut::Occupied(_) => false,
+            RawEntryMut::Vacant(entry) => {
+                entry.insert_hashed_nocheck(hash, node.key(), ());
+                true
+            }
+        }
+    }
 }
 
 impl std::cmp::PartialEq for TreeNode {
@@ -26,6 +126,8 @@ impl std::cmp::PartialEq for TreeNode {
             && self.weight == other.weight
             && self.flag_state == other.flag_state
             && self.string == other.string
+            && self.surface == other.surface
+            && self.edits == other.edits
     }
 }
 
@@ -62,6 +164,8 @@ impl lifeguard::Recycleable for TreeNode {
     fn new() -> Self {
         TreeNode {
             string: Vec::with_capacity(1),
+            surface: vec![],
+            edits: vec![],
             input_state: 0,
             mutator_state: 0,
             lexicon_state: 0,
@@ -83,6 +187,16 @@ impl lifeguard::InitializeWith<&TreeNode> for TreeNode {
             self.string.extend(&source.string);
         }
 
+        if self.surface != source.surface {
+            self.surface.truncate(0);
+            self.surface.extend(&source.surface);
+        }
+
+        if self.edits != source.edits {
+            self.edits.truncate(0);
+            self.edits.extend(&source.edits);
+        }
+
         self.input_state = source.input_state;
         self.mutator_state = source.mutator_state;
         self.lexicon_state = source.lexicon_state;
@@ -98,6 +212,17 @@ impl lifeguard::InitializeWith<&TreeNode> for TreeNode {
 }
 
 impl TreeNode {
+    pub fn key(&self) -> NodeKey {
+        NodeKey {
+            input_state: self.input_state,
+            mutator_state: self.mutator_state,
+            lexicon_state: self.lexicon_state,
+            flag_state: self.flag_state.clone(),
+            string: self.string.clone(),
+            surface: self.surface.clone(),
+        }
+    }
+
     #[inline(always)]
     pub fn empty<'a>(
         pool: &'a Pool<TreeNode>,
@@ -105,6 +230,8 @@ impl TreeNode {
     ) -> Recycled<'a, TreeNode> {
         pool.attach(TreeNode {
             string: vec![],
+            surface: vec![],
+            edits: vec![],
             input_state: 0,
             mutator_state: 0,
             lexicon_state: 0,
@@ -131,6 +258,16 @@ impl TreeNode {
             node.string.extend(&self.string);
         }
 
+        if node.surface != self.surface {
+            node.surface.truncate(0);
+            node.surface.extend(&self.surface);
+        }
+
+        if node.edits != self.edits {
+            node.edits.truncate(0);
+            node.edits.extend(&self.edits);
+        }
+
         if let Some(value) = transition.symbol() {
             if value != 0 {
                 node.string.push(value);
@@ -163,6 +300,16 @@ impl TreeNode {
             node.string.truncate(0);
             node.string.extend(&self.string);
         }
+
+        if node.surface != self.surface {
+            node.surface.truncate(0);
+            node.surface.extend(&self.surface);
+        }
+
+        if node.edits != self.edits {
+            node.edits.truncate(0);
+            node.edits.extend(&self.edits);
+        }
         node.input_state = self.input_state;
         node.mutator_state = transition.target().unwrap();
         node.lexicon_state = self.lexicon_state;
@@ -194,6 +341,16 @@ impl TreeNode {
             node.string.extend(&self.string);
         }
 
+        if node.surface != self.surface {
+            node.surface.truncate(0);
+            node.surface.extend(&self.surface);
+        }
+
+        if node.edits != self.edits {
+            node.edits.truncate(0);
+            node.edits.extend(&self.edits);
+        }
+
         if output_symbol != 0 {
             node.string.push(output_symbol);
         }
@@ -244,6 +401,16 @@ impl TreeNode {
             node.string.extend(&self.string);
         }
 
+        if node.surface != self.surface {
+            node.surface.truncate(0);
+            node.surface.extend(&self.surface);
+        }
+
+        if node.edits != self.edits {
+            node.edits.truncate(0);
+            node.edits.extend(&self.edits);
+        }
+
         node.input_state = self.input_state;
         node.mutator_state = self.mutator_state;
         node.lexicon_state = transition.target().unwrap();
@@ -313,3 +480,38 @@ impl TreeNode {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn visited_nodes() {
+        let pool = Pool::with_size_and_max(0, 0);
+        let mut visited = VisitedNodes::new();
+
+        let mut node = TreeNode::empty(&pool, vec![0; 2]).detach();
+        node.string = vec![1, 2];
+        assert!(visited.insert(&node));
+
+        // Weight and edits are not part of the configuration.
+        let mut heavier = node.clone();
+        heavier.weight = 1.0;
+        heavier.edits.push(MutatorEdit {
+            input_state: 0,
+            input: 1,
+            output: 2,
+            weight: 1.0,
+        });
+        assert!(!visited.insert(&heavier));
+
+        let mut other = node.clone();
+        other.flag_state[1] = 1;
+        assert!(visited.insert(&other));
+
+        let mut other = node.clone();
+        other.string.push(3);
+        assert!(visited.insert(&other));
+        assert!(!visited.insert(&other));
+    }
+}